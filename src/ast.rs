@@ -1,27 +1,155 @@
-use crate::token::Token;
+use crate::token::{Span, Token};
 
-use std::any::Any;
-
-pub trait Node: Any {
+pub trait Node {
     fn token_litteral(&self) -> String;
     fn to_string(&self) -> String;
+    /// location of the whole node in the source
+    fn span(&self) -> Span;
 }
 
 /// statement nodes (doesn't produce a value)
-pub trait Statement: Node {}
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Let(LetStatement),
+    Return(ReturnStatement),
+    Expression(ExpressionStatement),
+    Assign(AssignStatement),
+    While(WhileStatement),
+    For(ForStatement),
+    Break(BreakStatement),
+    Continue(ContinueStatement),
+    Error(ErrorStatement), // statement that could not be parsed
+}
+
+impl Node for Statement {
+    fn token_litteral(&self) -> String {
+        return match self {
+            Statement::Let(statement) => statement.token_litteral(),
+            Statement::Return(statement) => statement.token_litteral(),
+            Statement::Expression(statement) => statement.token_litteral(),
+            Statement::Assign(statement) => statement.token_litteral(),
+            Statement::While(statement) => statement.token_litteral(),
+            Statement::For(statement) => statement.token_litteral(),
+            Statement::Break(statement) => statement.token_litteral(),
+            Statement::Continue(statement) => statement.token_litteral(),
+            Statement::Error(statement) => statement.token_litteral(),
+        };
+    }
+
+    fn to_string(&self) -> String {
+        return match self {
+            Statement::Let(statement) => statement.to_string(),
+            Statement::Return(statement) => statement.to_string(),
+            Statement::Expression(statement) => statement.to_string(),
+            Statement::Assign(statement) => statement.to_string(),
+            Statement::While(statement) => statement.to_string(),
+            Statement::For(statement) => statement.to_string(),
+            Statement::Break(statement) => statement.to_string(),
+            Statement::Continue(statement) => statement.to_string(),
+            Statement::Error(statement) => statement.to_string(),
+        };
+    }
+
+    fn span(&self) -> Span {
+        return match self {
+            Statement::Let(statement) => statement.span(),
+            Statement::Return(statement) => statement.span(),
+            Statement::Expression(statement) => statement.span(),
+            Statement::Assign(statement) => statement.span(),
+            Statement::While(statement) => statement.span(),
+            Statement::For(statement) => statement.span(),
+            Statement::Break(statement) => statement.span(),
+            Statement::Continue(statement) => statement.span(),
+            Statement::Error(statement) => statement.span(),
+        };
+    }
+}
 
 // expression nodes (produces a value -- ex: add(5, 5) or 5)
-pub trait Expression: Node {}
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Identifier(Identifier),
+    Integer(IntegerLiteral),
+    Float(FloatLiteral),
+    String(StringLiteral),
+    Boolean(Boolean),
+    Prefix(PrefixExpression),
+    Infix(InfixExpression),
+    If(IfExpression),
+    Function(FunctionLiteral),
+    Call(CallExpression),
+    Array(ArrayLiteral),
+    Index(IndexExpression),
+    Hash(HashLiteral),
+}
+
+impl Node for Expression {
+    fn token_litteral(&self) -> String {
+        return match self {
+            Expression::Identifier(expression) => expression.token_litteral(),
+            Expression::Integer(expression) => expression.token_litteral(),
+            Expression::Float(expression) => expression.token_litteral(),
+            Expression::String(expression) => expression.token_litteral(),
+            Expression::Boolean(expression) => expression.token_litteral(),
+            Expression::Prefix(expression) => expression.token_litteral(),
+            Expression::Infix(expression) => expression.token_litteral(),
+            Expression::If(expression) => expression.token_litteral(),
+            Expression::Function(expression) => expression.token_litteral(),
+            Expression::Call(expression) => expression.token_litteral(),
+            Expression::Array(expression) => expression.token_litteral(),
+            Expression::Index(expression) => expression.token_litteral(),
+            Expression::Hash(expression) => expression.token_litteral(),
+        };
+    }
+
+    fn to_string(&self) -> String {
+        return match self {
+            Expression::Identifier(expression) => expression.to_string(),
+            Expression::Integer(expression) => expression.to_string(),
+            Expression::Float(expression) => expression.to_string(),
+            Expression::String(expression) => expression.to_string(),
+            Expression::Boolean(expression) => expression.to_string(),
+            Expression::Prefix(expression) => expression.to_string(),
+            Expression::Infix(expression) => expression.to_string(),
+            Expression::If(expression) => expression.to_string(),
+            Expression::Function(expression) => expression.to_string(),
+            Expression::Call(expression) => expression.to_string(),
+            Expression::Array(expression) => expression.to_string(),
+            Expression::Index(expression) => expression.to_string(),
+            Expression::Hash(expression) => expression.to_string(),
+        };
+    }
+
+    fn span(&self) -> Span {
+        return match self {
+            Expression::Identifier(expression) => expression.span(),
+            Expression::Integer(expression) => expression.span(),
+            Expression::Float(expression) => expression.span(),
+            Expression::String(expression) => expression.span(),
+            Expression::Boolean(expression) => expression.span(),
+            Expression::Prefix(expression) => expression.span(),
+            Expression::Infix(expression) => expression.span(),
+            Expression::If(expression) => expression.span(),
+            Expression::Function(expression) => expression.span(),
+            Expression::Call(expression) => expression.span(),
+            Expression::Array(expression) => expression.span(),
+            Expression::Index(expression) => expression.span(),
+            Expression::Hash(expression) => expression.span(),
+        };
+    }
+}
 
 // ======================================================
 // Root Program Node
+#[derive(Debug, Clone, PartialEq)]
 pub struct Program {
-    pub statements: Vec<Box<dyn Statement>>,
+    pub statements: Vec<Statement>,
+    pub span: Span,
 }
 
 impl Node for Program {
     fn token_litteral(&self) -> String {
-        if self.statements.len() > 0 {
+        if !self.statements.is_empty() {
             return self.statements[0].token_litteral();
         } else {
             return "".to_string();
@@ -29,21 +157,53 @@ impl Node for Program {
     }
 
     fn to_string(&self) -> String {
-        let mut buf = String::new();
+        return join_statements(&self.statements, "\n");
+    }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
+}
+
+/// Prints the statements so that they parse back the same way:
+/// an expression statement followed by another statement needs its `;`
+/// (ex: `f` then `(1)` would be read back as the call `f(1)`)
+fn join_statements(statements: &[Statement], separator: &str) -> String {
+    let mut buf = String::new();
 
-        for statement in &self.statements {
-            buf += &statement.to_string();
+    for (i, statement) in statements.iter().enumerate() {
+        if i > 0 {
+            buf += separator;
         }
 
-        return buf;
+        buf += &statement.to_string();
+
+        if matches!(statement, Statement::Expression(_)) && i + 1 < statements.len() {
+            buf += ";";
+        }
     }
+
+    return buf;
+}
+
+/// Condition of an `if` or a `while`, which has to be in parentheses
+/// (prefix, infix and index expressions already print their own)
+fn condition_to_string(condition: &Expression) -> String {
+    return match condition {
+        Expression::Prefix(_) | Expression::Infix(_) | Expression::Index(_) => {
+            condition.to_string()
+        }
+        _ => format!("({})", condition.to_string()),
+    };
 }
 
 // ======================================================
 // Expression Statement Node
+#[derive(Debug, Clone, PartialEq)]
 pub struct ExpressionStatement {
     pub token: Token, // the first token of the statement
-    pub expression: Box<dyn Expression>,
+    pub expression: Expression,
+    pub span: Span,
 }
 
 impl Node for ExpressionStatement {
@@ -54,15 +214,21 @@ impl Node for ExpressionStatement {
     fn to_string(&self) -> String {
         return self.expression.to_string();
     }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
 }
-impl Statement for ExpressionStatement {}
 
 // ======================================================
 // Let Statement Node
+#[derive(Debug, Clone, PartialEq)]
 pub struct LetStatement {
     pub token: Token,
     pub identifier: Identifier,
-    pub value: Box<dyn Expression>,
+    pub value: Expression,
+    pub doc: Option<String>, // `///` comments in front of the statement
+    pub span: Span,
 }
 
 impl Node for LetStatement {
@@ -71,21 +237,35 @@ impl Node for LetStatement {
     }
 
     fn to_string(&self) -> String {
-        return format!(
+        let mut buf = String::new();
+        if let Some(doc) = &self.doc {
+            for line in doc.lines() {
+                buf += &format!("/// {}\n", line);
+            }
+        }
+
+        buf += &format!(
             "{} {} = {};",
             &self.token.litteral(),
             &self.identifier.to_string(),
             &self.value.to_string()
         );
+
+        return buf;
+    }
+
+    fn span(&self) -> Span {
+        return self.span;
     }
 }
-impl Statement for LetStatement {}
 
 // ======================================================
 // Return Statement Node
+#[derive(Debug, Clone, PartialEq)]
 pub struct ReturnStatement {
     pub token: Token,
-    pub return_value: Box<dyn Expression>,
+    pub return_value: Expression,
+    pub span: Span,
 }
 
 impl Node for ReturnStatement {
@@ -100,15 +280,187 @@ impl Node for ReturnStatement {
             self.return_value.to_string()
         );
     }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
+}
+
+// ======================================================
+// Assign Statement Node
+// rebinding of an existing name (ex: `x = 1` or `x += 1`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssignStatement {
+    pub token: Token, // the assignment operator
+    pub identifier: Identifier,
+    pub value: Expression,
+    pub span: Span,
+}
+
+impl AssignStatement {
+    /// Infix operator combining the current value with the assigned one (ex: `+` for `+=`)
+    pub fn operator(&self) -> Option<&'static str> {
+        return match self.token {
+            Token::PlusAssign => Some("+"),
+            Token::MinusAssign => Some("-"),
+            Token::AsteriskAssign => Some("*"),
+            Token::SlashAssign => Some("/"),
+            _ => None,
+        };
+    }
+}
+
+impl Node for AssignStatement {
+    fn token_litteral(&self) -> String {
+        return self.token.litteral();
+    }
+
+    fn to_string(&self) -> String {
+        return format!(
+            "{} {} {};",
+            &self.identifier.to_string(),
+            &self.token.litteral(),
+            &self.value.to_string()
+        );
+    }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
+}
+
+// ======================================================
+// While Statement Node (ex: while (x < 10) { x += 1; })
+#[derive(Debug, Clone, PartialEq)]
+pub struct WhileStatement {
+    pub token: Token,
+    pub condition: Expression,
+    pub body: BlockStatement,
+    pub span: Span,
+}
+
+impl Node for WhileStatement {
+    fn token_litteral(&self) -> String {
+        return self.token.litteral();
+    }
+
+    fn to_string(&self) -> String {
+        return format!(
+            "while {} {}",
+            condition_to_string(&self.condition),
+            self.body.to_string()
+        );
+    }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
+}
+
+// ======================================================
+// For Statement Node (ex: for (x in [1, 2]) { puts(x); })
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForStatement {
+    pub token: Token,
+    pub variable: Identifier, // bound to each element in turn, in the scope of the loop
+    pub iterable: Expression,
+    pub body: BlockStatement,
+    pub span: Span,
+}
+
+impl Node for ForStatement {
+    fn token_litteral(&self) -> String {
+        return self.token.litteral();
+    }
+
+    fn to_string(&self) -> String {
+        return format!(
+            "for ({} in {}) {}",
+            self.variable.to_string(),
+            self.iterable.to_string(),
+            self.body.to_string()
+        );
+    }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
 }
 
-impl Statement for ReturnStatement {}
+// ======================================================
+// Break Statement Node
+#[derive(Debug, Clone, PartialEq)]
+pub struct BreakStatement {
+    pub token: Token,
+    pub span: Span,
+}
+
+impl Node for BreakStatement {
+    fn token_litteral(&self) -> String {
+        return self.token.litteral();
+    }
+
+    fn to_string(&self) -> String {
+        return "break;".to_string();
+    }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
+}
+
+// ======================================================
+// Continue Statement Node
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContinueStatement {
+    pub token: Token,
+    pub span: Span,
+}
+
+impl Node for ContinueStatement {
+    fn token_litteral(&self) -> String {
+        return self.token.litteral();
+    }
+
+    fn to_string(&self) -> String {
+        return "continue;".to_string();
+    }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
+}
+
+// ======================================================
+// Error Statement Node
+// tokens skipped by the parser to recover from a syntax error
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorStatement {
+    pub token: Token, // the first token of the statement
+    pub span: Span,
+}
+
+impl Node for ErrorStatement {
+    fn token_litteral(&self) -> String {
+        return self.token.litteral();
+    }
+
+    fn to_string(&self) -> String {
+        return "<error>".to_string();
+    }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
+}
 
 // ======================================================
 // Identifier Node
+#[derive(Debug, Clone, PartialEq)]
 pub struct Identifier {
     pub token: Token,
     pub value: String,
+    pub span: Span,
 }
 
 impl Node for Identifier {
@@ -117,7 +469,9 @@ impl Node for Identifier {
             Token::Ident(value) => value.clone(),
             _ => {
                 let tok = &self.token;
-                unreachable!("It should be impossible to reach other token types in an expression identifier: {tok}")
+                unreachable!(
+                    "It should be impossible to reach other token types in an expression identifier: {tok}"
+                )
             }
         }
     }
@@ -125,30 +479,392 @@ impl Node for Identifier {
     fn to_string(&self) -> String {
         return self.value.clone();
     }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
+}
+
+// ======================================================
+// Integer Literal Node
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntegerLiteral {
+    pub token: Token,
+    pub value: i64,
+    pub span: Span,
+}
+
+impl Node for IntegerLiteral {
+    fn token_litteral(&self) -> String {
+        return self.token.litteral();
+    }
+
+    fn to_string(&self) -> String {
+        return self.token.litteral();
+    }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
+}
+
+// ======================================================
+// Float Literal Node
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloatLiteral {
+    pub token: Token,
+    pub value: f64,
+    pub span: Span,
+}
+
+impl Node for FloatLiteral {
+    fn token_litteral(&self) -> String {
+        return self.token.litteral();
+    }
+
+    fn to_string(&self) -> String {
+        return self.token.litteral();
+    }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
+}
+
+// ======================================================
+// String Literal Node
+#[derive(Debug, Clone, PartialEq)]
+pub struct StringLiteral {
+    pub token: Token,
+    pub value: String,
+    pub span: Span,
+}
+
+impl Node for StringLiteral {
+    fn token_litteral(&self) -> String {
+        return self.token.litteral();
+    }
+
+    /// Quoted and escaped back (so that it can be lexed again)
+    fn to_string(&self) -> String {
+        return format!("{:?}", self.value);
+    }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
+}
+
+// ======================================================
+// Boolean Node
+#[derive(Debug, Clone, PartialEq)]
+pub struct Boolean {
+    pub token: Token,
+    pub value: bool,
+    pub span: Span,
+}
+
+impl Node for Boolean {
+    fn token_litteral(&self) -> String {
+        return self.token.litteral();
+    }
+
+    fn to_string(&self) -> String {
+        return self.token.litteral();
+    }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
+}
+
+// ======================================================
+// Prefix Expression Node (ex: -5 or !true)
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrefixExpression {
+    pub token: Token, // the prefix operator token
+    pub operator: String,
+    pub right: Box<Expression>,
+    pub span: Span,
+}
+
+impl Node for PrefixExpression {
+    fn token_litteral(&self) -> String {
+        return self.token.litteral();
+    }
+
+    fn to_string(&self) -> String {
+        return format!("({}{})", self.operator, self.right.to_string());
+    }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
+}
+
+// ======================================================
+// Infix Expression Node (ex: 5 + 5)
+#[derive(Debug, Clone, PartialEq)]
+pub struct InfixExpression {
+    pub token: Token, // the operator token
+    pub left: Box<Expression>,
+    pub operator: String,
+    pub right: Box<Expression>,
+    pub span: Span,
+}
+
+impl Node for InfixExpression {
+    fn token_litteral(&self) -> String {
+        return self.token.litteral();
+    }
+
+    fn to_string(&self) -> String {
+        return format!(
+            "({} {} {})",
+            self.left.to_string(),
+            self.operator,
+            self.right.to_string()
+        );
+    }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
+}
+
+// ======================================================
+// Block Statement Node (ex: { x; y; })
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockStatement {
+    pub token: Token, // the { token
+    pub statements: Vec<Statement>,
+    pub span: Span,
+}
+
+impl Node for BlockStatement {
+    fn token_litteral(&self) -> String {
+        return self.token.litteral();
+    }
+
+    fn to_string(&self) -> String {
+        if self.statements.is_empty() {
+            return "{}".to_string();
+        }
+
+        return format!("{{ {} }}", join_statements(&self.statements, " "));
+    }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
+}
+
+// ======================================================
+// If Expression Node (ex: if (x < y) { x } else { y })
+#[derive(Debug, Clone, PartialEq)]
+pub struct IfExpression {
+    pub token: Token, // the if token
+    pub condition: Box<Expression>,
+    pub consequence: BlockStatement,
+    pub alternative: Option<BlockStatement>,
+    pub span: Span,
+}
+
+impl Node for IfExpression {
+    fn token_litteral(&self) -> String {
+        return self.token.litteral();
+    }
+
+    fn to_string(&self) -> String {
+        let mut buf = format!(
+            "if {} {}",
+            condition_to_string(&self.condition),
+            self.consequence.to_string()
+        );
+
+        if let Some(alternative) = &self.alternative {
+            buf += &format!(" else {}", alternative.to_string());
+        }
+
+        return buf;
+    }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
+}
+
+// ======================================================
+// Function Literal Node (ex: fn(x, y) { x + y; })
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionLiteral {
+    pub token: Token, // the fn token
+    pub parameters: Vec<Identifier>,
+    pub body: BlockStatement,
+    pub span: Span,
+}
+
+impl Node for FunctionLiteral {
+    fn token_litteral(&self) -> String {
+        return self.token.litteral();
+    }
+
+    fn to_string(&self) -> String {
+        let parameters: Vec<String> = self.parameters.iter().map(|p| p.to_string()).collect();
+
+        return format!(
+            "{}({}) {}",
+            self.token_litteral(),
+            parameters.join(", "),
+            self.body.to_string()
+        );
+    }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
+}
+
+// ======================================================
+// Call Expression Node (ex: add(1, 2))
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallExpression {
+    pub token: Token,              // the ( token
+    pub function: Box<Expression>, // Identifier or FunctionLiteral
+    pub arguments: Vec<Expression>,
+    pub span: Span,
+}
+
+impl Node for CallExpression {
+    fn token_litteral(&self) -> String {
+        return self.token.litteral();
+    }
+
+    fn to_string(&self) -> String {
+        let arguments: Vec<String> = self.arguments.iter().map(|a| a.to_string()).collect();
+
+        return format!("{}({})", self.function.to_string(), arguments.join(", "));
+    }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
+}
+
+// ======================================================
+// Array Literal Node (ex: [1, 2])
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrayLiteral {
+    pub token: Token, // the [ token
+    pub elements: Vec<Expression>,
+    pub span: Span,
+}
+
+impl Node for ArrayLiteral {
+    fn token_litteral(&self) -> String {
+        return self.token.litteral();
+    }
+
+    fn to_string(&self) -> String {
+        let elements: Vec<String> = self.elements.iter().map(|e| e.to_string()).collect();
+
+        return format!("[{}]", elements.join(", "));
+    }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
+}
+
+// ======================================================
+// Index Expression Node (ex: my_array[1])
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexExpression {
+    pub token: Token, // the [ token
+    pub left: Box<Expression>,
+    pub index: Box<Expression>,
+    pub span: Span,
+}
+
+impl Node for IndexExpression {
+    fn token_litteral(&self) -> String {
+        return self.token.litteral();
+    }
+
+    fn to_string(&self) -> String {
+        return format!("({}[{}])", self.left.to_string(), self.index.to_string());
+    }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
+}
+
+// ======================================================
+// Hash Literal Node (ex: {"a": 1, true: 2})
+#[derive(Debug, Clone, PartialEq)]
+pub struct HashLiteral {
+    pub token: Token,                         // the { token
+    pub pairs: Vec<(Expression, Expression)>, // in source order
+    pub span: Span,
+}
+
+impl Node for HashLiteral {
+    fn token_litteral(&self) -> String {
+        return self.token.litteral();
+    }
+
+    fn to_string(&self) -> String {
+        let pairs: Vec<String> = self
+            .pairs
+            .iter()
+            .map(|(key, value)| format!("{}: {}", key.to_string(), value.to_string()))
+            .collect();
+
+        return format!("{{{}}}", pairs.join(", "));
+    }
+
+    fn span(&self) -> Span {
+        return self.span;
+    }
 }
-impl Expression for Identifier {}
 
 #[cfg(test)]
 mod tests {
-    use super::{Identifier, LetStatement, Node, Program, Statement, Token};
+    use super::{Expression, Identifier, LetStatement, Node, Program, Span, Statement, Token};
 
     #[test]
     fn test_string() {
-        let mut statements: Vec<Box<dyn Statement>> = Vec::new();
-        statements.push(Box::new(LetStatement {
+        let statements = vec![Statement::Let(LetStatement {
             token: Token::Let,
             identifier: Identifier {
                 token: Token::Ident("my_var".to_string()),
                 value: "my_var".to_string(),
+                span: Span::default(),
             },
-            value: Box::new(Identifier {
+            value: Expression::Identifier(Identifier {
                 token: Token::Ident("another_var".to_string()),
                 value: "another_var".to_string(),
+                span: Span::default(),
             }),
-        }));
+            doc: None,
+            span: Span::default(),
+        })];
+
+        let mut program = Program {
+            statements,
+            span: Span::default(),
+        };
 
-        let program = Program { statements };
+        assert_eq!(program.to_string(), "let my_var = another_var;");
 
-        assert_eq!(program.to_string(), "let my_var = another_var;")
+        if let Statement::Let(statement) = &mut program.statements[0] {
+            statement.doc = Some("The variable\nof the test".to_string());
+        }
+
+        assert_eq!(
+            program.to_string(),
+            "/// The variable\n/// of the test\nlet my_var = another_var;"
+        );
     }
 }
@@ -0,0 +1,410 @@
+use crate::ast::{Node, Program, Statement};
+use crate::compiler::Compiler;
+use crate::diagnostic::Diagnostic;
+use crate::evaluator::{self, Environment, Object};
+use crate::lexer::Lexer;
+use crate::parser::{Parser, lexer_error};
+use crate::repl;
+use crate::vm::Vm;
+
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: compiler [COMMAND] [OPTIONS]
+
+Commands:
+  repl                 start the interactive REPL (default)
+  run <file>           run a program and print the value of its last expression
+  check <file>         report the errors of a program without running it
+  tokens <file>        print the tokens of a program
+  ast <file>           print the parsed program
+  disasm <file>        print the compiled instructions and constants of a program
+
+Options:
+  --engine=eval|vm     engine used by `run` (default: eval)
+  -h, --help           show this message";
+
+// syntax, compilation or runtime error in the program
+const EXIT_FAILURE: u8 = 1;
+// bad command line or unreadable file
+pub(crate) const EXIT_USAGE: u8 = 2;
+
+/// What runs the programs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    Eval, // tree-walking evaluator
+    Vm,   // bytecode compiler and virtual machine
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Repl,
+    Run { path: String, engine: Engine },
+    Check { path: String },
+    Tokens { path: String },
+    Ast { path: String },
+    Disasm { path: String },
+    Help,
+}
+
+/// Reads the command out of the arguments (program name excluded)
+pub fn parse_args(args: &[String]) -> Result<Command, String> {
+    let mut engine = None;
+    let mut positionals = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            return Ok(Command::Help);
+        }
+
+        let value = if let Some(value) = arg.strip_prefix("--engine=") {
+            value
+        } else if arg == "--engine" {
+            match args.next() {
+                Some(value) => value,
+                None => return Err("missing value for `--engine`".to_string()),
+            }
+        } else if arg.starts_with('-') {
+            return Err(format!("unknown option `{}`", arg));
+        } else {
+            positionals.push(arg.as_str());
+            continue;
+        };
+
+        engine = match value {
+            "eval" => Some(Engine::Eval),
+            "vm" => Some(Engine::Vm),
+            _ => return Err(format!("unknown engine `{}`, expected eval or vm", value)),
+        };
+    }
+
+    let (name, rest) = match positionals.split_first() {
+        Some((name, rest)) => (*name, rest),
+        None => ("repl", &[][..]),
+    };
+
+    if !["repl", "run", "check", "tokens", "ast", "disasm"].contains(&name) {
+        return Err(format!("unknown command `{}`", name));
+    }
+
+    if engine.is_some() && name != "run" {
+        return Err(format!("`--engine` can not be used with `{}`", name));
+    }
+
+    if name == "repl" {
+        if !rest.is_empty() {
+            return Err("`repl` does not take a file".to_string());
+        }
+        return Ok(Command::Repl);
+    }
+
+    let path = match rest {
+        [path] => path.to_string(),
+        [] => return Err(format!("`{}` expects a file", name)),
+        _ => return Err(format!("`{}` expects a single file", name)),
+    };
+
+    return match name {
+        "run" => Ok(Command::Run {
+            path,
+            engine: engine.unwrap_or(Engine::Eval),
+        }),
+        "check" => Ok(Command::Check { path }),
+        "tokens" => Ok(Command::Tokens { path }),
+        "ast" => Ok(Command::Ast { path }),
+        "disasm" => Ok(Command::Disasm { path }),
+        _ => unreachable!("Unknown commands are rejected above"),
+    };
+}
+
+pub fn execute(command: Command) -> ExitCode {
+    let path = match &command {
+        Command::Help => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Command::Repl => {
+            return match repl::start() {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    ExitCode::from(EXIT_FAILURE)
+                }
+            };
+        }
+        Command::Run { path, .. }
+        | Command::Check { path }
+        | Command::Tokens { path }
+        | Command::Ast { path }
+        | Command::Disasm { path } => path,
+    };
+
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("error: could not read `{}`: {}", path, err);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    if let Command::Tokens { .. } = command {
+        let (listing, diagnostics) = tokens(&source);
+        print!("{}", listing);
+
+        for diagnostic in &diagnostics {
+            eprintln!("{}\n", diagnostic.render(&source, path));
+        }
+        if !diagnostics.is_empty() {
+            return ExitCode::from(EXIT_FAILURE);
+        }
+
+        return ExitCode::SUCCESS;
+    }
+
+    let program = match parse(&source) {
+        Ok(program) => program,
+        Err(diagnostics) => {
+            for diagnostic in diagnostics {
+                eprintln!("{}\n", diagnostic.render(&source, path));
+            }
+            return ExitCode::from(EXIT_FAILURE);
+        }
+    };
+
+    let result = match command {
+        Command::Run { engine, .. } => run(&program, engine).map(|value| {
+            if let Some(value) = value {
+                println!("{}", value);
+            }
+        }),
+        Command::Check { .. } => compile(&program).map(|_| println!("{}: ok", path)),
+        Command::Ast { .. } => {
+            println!("{}", program.to_string());
+            Ok(())
+        }
+        Command::Disasm { .. } => compile(&program).map(|compiler| {
+            println!("{}", compiler.bytecode().disassemble());
+        }),
+        _ => unreachable!("Handled before parsing"),
+    };
+
+    return match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("error: {}: {}", path, message);
+            ExitCode::from(EXIT_FAILURE)
+        }
+    };
+}
+
+// ======================================================
+// Pipeline stages
+
+/// One token per line, prefixed by its location, along with the errors of the invalid tokens
+fn tokens(source: &str) -> (String, Vec<Diagnostic>) {
+    let mut buf = String::new();
+    let mut diagnostics = Vec::new();
+
+    for spanned in Lexer::new(source) {
+        buf += &format!(
+            "{}:{} {}\n",
+            spanned.span.line, spanned.span.column, spanned.token
+        );
+
+        if let Some(diagnostic) = lexer_error(&spanned.token, spanned.span) {
+            diagnostics.push(diagnostic);
+        }
+    }
+
+    return (buf, diagnostics);
+}
+
+fn parse(source: &str) -> Result<Program, Vec<Diagnostic>> {
+    let mut lexer = Lexer::new(source);
+    let mut parser = Parser::new(&mut lexer);
+
+    let program = parser.parse_program();
+
+    if !parser.get_errors().is_empty() {
+        return Err(parser.get_errors().clone());
+    }
+
+    return Ok(program);
+}
+
+fn compile(program: &Program) -> Result<Compiler, String> {
+    let mut compiler = Compiler::new();
+    compiler.compile(program)?;
+
+    return Ok(compiler);
+}
+
+/// Value of the program, None if it ends with a `let`, an assignment or a loop
+/// (unless a top level `return` stopped it before)
+pub fn run(program: &Program, engine: Engine) -> Result<Option<Object>, String> {
+    let (value, returned) = match engine {
+        Engine::Eval => match evaluator::eval_program(program, &Environment::new()) {
+            (Object::Error(message), _) => return Err(message),
+            result => result,
+        },
+        Engine::Vm => {
+            let compiler = compile(program)?;
+
+            let mut vm = Vm::new(compiler.bytecode());
+            vm.run()?;
+
+            (vm.last_popped_stack_elem().clone(), vm.returned())
+        }
+    };
+
+    let valueless = matches!(
+        program.statements.last(),
+        Some(Statement::Let(_) | Statement::Assign(_) | Statement::While(_) | Statement::For(_))
+            | None
+    );
+
+    if valueless && !returned {
+        return Ok(None);
+    }
+
+    return Ok(Some(value));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, Engine, Object, parse, parse_args, run, tokens};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        return args.iter().map(|arg| arg.to_string()).collect();
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let path = "main.monkey".to_string();
+
+        let tests = [
+            (args(&[]), Ok(Command::Repl)),
+            (args(&["repl"]), Ok(Command::Repl)),
+            (args(&["--help"]), Ok(Command::Help)),
+            (
+                args(&["run", "main.monkey"]),
+                Ok(Command::Run {
+                    path: path.clone(),
+                    engine: Engine::Eval,
+                }),
+            ),
+            (
+                args(&["run", "--engine=vm", "main.monkey"]),
+                Ok(Command::Run {
+                    path: path.clone(),
+                    engine: Engine::Vm,
+                }),
+            ),
+            (
+                args(&["run", "main.monkey", "--engine", "eval"]),
+                Ok(Command::Run {
+                    path: path.clone(),
+                    engine: Engine::Eval,
+                }),
+            ),
+            (
+                args(&["check", "main.monkey"]),
+                Ok(Command::Check { path: path.clone() }),
+            ),
+            (
+                args(&["tokens", "main.monkey"]),
+                Ok(Command::Tokens { path: path.clone() }),
+            ),
+            (
+                args(&["ast", "main.monkey"]),
+                Ok(Command::Ast { path: path.clone() }),
+            ),
+            (
+                args(&["disasm", "main.monkey"]),
+                Ok(Command::Disasm { path: path.clone() }),
+            ),
+            (args(&["run"]), Err("`run` expects a file")),
+            (args(&["run", "a", "b"]), Err("`run` expects a single file")),
+            (
+                args(&["run", "--engine=jit", "a"]),
+                Err("unknown engine `jit`, expected eval or vm"),
+            ),
+            (
+                args(&["check", "--engine=vm", "a"]),
+                Err("`--engine` can not be used with `check`"),
+            ),
+            (args(&["build", "a"]), Err("unknown command `build`")),
+            (args(&["build"]), Err("unknown command `build`")),
+            (args(&["-x"]), Err("unknown option `-x`")),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                parse_args(&input),
+                expected.map_err(|msg| msg.to_string()),
+                "Wrong command for {input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_run_engines() {
+        let tests = [
+            (
+                "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(10)",
+                Ok(Some(Object::Integer(55))),
+            ),
+            ("let x = 5;", Ok(None)),
+            ("", Ok(None)),
+            ("return 1; 2", Ok(Some(Object::Integer(1)))),
+            ("return 3; let a = 1", Ok(Some(Object::Integer(3)))),
+            (
+                "let i = 0; while (true) { i += 1; if (i == 4) { return i; } }",
+                Ok(Some(Object::Integer(4))),
+            ),
+            ("let a = 1; if (a == 2) { return a; }; a = 3", Ok(None)),
+            (
+                "1 + true",
+                Err("type mismatch: INTEGER + BOOLEAN".to_string()),
+            ),
+        ];
+
+        for engine in [Engine::Eval, Engine::Vm] {
+            for (input, expected) in &tests {
+                let program = parse(input).expect("Program should parse");
+                assert_eq!(
+                    run(&program, engine),
+                    *expected,
+                    "Wrong result for `{input}` with {engine:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_tokens() {
+        let (listing, diagnostics) = tokens("let x\n= 1;");
+        assert_eq!(
+            listing,
+            "1:1 Let\n1:5 Ident(x)\n2:1 Assign\n2:3 Int(1)\n2:4 Semicolon\n2:5 Eof\n"
+        );
+        assert!(diagnostics.is_empty());
+
+        let (listing, diagnostics) = tokens("1 @ 0b2");
+        assert_eq!(
+            listing,
+            "1:1 Int(1)\n1:3 Illegal(@)\n1:5 InvalidNumber(invalid digit `2` in binary literal)\n1:8 Eof\n"
+        );
+        let messages: Vec<&str> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Illegal character `@`",
+                "Invalid number: invalid digit `2` in binary literal"
+            ]
+        );
+    }
+}
@@ -0,0 +1,334 @@
+use std::fmt::Display;
+
+/// Encoded bytecode: an opcode byte followed by its big-endian operands
+pub type Instructions = Vec<u8>;
+
+#[repr(u8)]
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Opcode {
+    Constant,
+    Pop,
+
+    // Arithmetic
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+
+    // Litterals
+    True,
+    False,
+    Null,
+
+    // Comparison
+    Equal,
+    NotEqual,
+    GreaterThan,
+    LessThan,
+    GreaterEqual,
+    LessEqual,
+
+    // Prefix operators
+    Minus,
+    Bang,
+
+    // Control flow
+    JumpNotTruthy,
+    Jump,
+    IterNext, // pops an array and an index, pushes the element at the index or jumps past the end
+
+    // Bindings
+    GetGlobal,
+    SetGlobal,
+    GetLocal,
+    SetLocal,    // binds a new variable (a closure may still hold the previous one)
+    AssignLocal, // writes to the variable, shared with the closures that captured it
+    GetFree,
+    AssignFree,
+    CaptureLocal, // pushes the variable itself (not its value) for a closure to share
+    CaptureFree,
+    GetBuiltin,
+
+    // Collections
+    Array,
+    Hash,
+    Index,
+
+    // Functions
+    Call,
+    ReturnValue,
+    Return,
+    Closure,
+    CurrentClosure,
+}
+
+/// Human readable name and width (in bytes) of each operand of an opcode
+pub struct Definition {
+    pub name: &'static str,
+    pub operand_widths: &'static [usize],
+}
+
+impl Opcode {
+    /// Operand-width table of the opcodes
+    pub fn definition(&self) -> Definition {
+        let (name, operand_widths): (&'static str, &'static [usize]) = match self {
+            Opcode::Constant => ("OpConstant", &[2]),
+            Opcode::Pop => ("OpPop", &[]),
+            Opcode::Add => ("OpAdd", &[]),
+            Opcode::Sub => ("OpSub", &[]),
+            Opcode::Mul => ("OpMul", &[]),
+            Opcode::Div => ("OpDiv", &[]),
+            Opcode::Mod => ("OpMod", &[]),
+            Opcode::True => ("OpTrue", &[]),
+            Opcode::False => ("OpFalse", &[]),
+            Opcode::Null => ("OpNull", &[]),
+            Opcode::Equal => ("OpEqual", &[]),
+            Opcode::NotEqual => ("OpNotEqual", &[]),
+            Opcode::GreaterThan => ("OpGreaterThan", &[]),
+            Opcode::LessThan => ("OpLessThan", &[]),
+            Opcode::GreaterEqual => ("OpGreaterEqual", &[]),
+            Opcode::LessEqual => ("OpLessEqual", &[]),
+            Opcode::Minus => ("OpMinus", &[]),
+            Opcode::Bang => ("OpBang", &[]),
+            Opcode::JumpNotTruthy => ("OpJumpNotTruthy", &[2]),
+            Opcode::Jump => ("OpJump", &[2]),
+            Opcode::IterNext => ("OpIterNext", &[2]),
+            Opcode::GetGlobal => ("OpGetGlobal", &[2]),
+            Opcode::SetGlobal => ("OpSetGlobal", &[2]),
+            Opcode::GetLocal => ("OpGetLocal", &[1]),
+            Opcode::SetLocal => ("OpSetLocal", &[1]),
+            Opcode::AssignLocal => ("OpAssignLocal", &[1]),
+            Opcode::GetFree => ("OpGetFree", &[1]),
+            Opcode::AssignFree => ("OpAssignFree", &[1]),
+            Opcode::CaptureLocal => ("OpCaptureLocal", &[1]),
+            Opcode::CaptureFree => ("OpCaptureFree", &[1]),
+            Opcode::GetBuiltin => ("OpGetBuiltin", &[1]),
+            Opcode::Array => ("OpArray", &[2]), // number of elements
+            Opcode::Hash => ("OpHash", &[2]),   // number of keys and values
+            Opcode::Index => ("OpIndex", &[]),
+            Opcode::Call => ("OpCall", &[1]), // number of arguments
+            Opcode::ReturnValue => ("OpReturnValue", &[]),
+            Opcode::Return => ("OpReturn", &[]),
+            Opcode::Closure => ("OpClosure", &[2, 1]), // constant index, number of free variables
+            Opcode::CurrentClosure => ("OpCurrentClosure", &[]),
+        };
+
+        return Definition {
+            name,
+            operand_widths,
+        };
+    }
+}
+
+// every opcode, in discriminant order (used to decode bytes)
+const OPCODES: [Opcode; 39] = [
+    Opcode::Constant,
+    Opcode::Pop,
+    Opcode::Add,
+    Opcode::Sub,
+    Opcode::Mul,
+    Opcode::Div,
+    Opcode::Mod,
+    Opcode::True,
+    Opcode::False,
+    Opcode::Null,
+    Opcode::Equal,
+    Opcode::NotEqual,
+    Opcode::GreaterThan,
+    Opcode::LessThan,
+    Opcode::GreaterEqual,
+    Opcode::LessEqual,
+    Opcode::Minus,
+    Opcode::Bang,
+    Opcode::JumpNotTruthy,
+    Opcode::Jump,
+    Opcode::IterNext,
+    Opcode::GetGlobal,
+    Opcode::SetGlobal,
+    Opcode::GetLocal,
+    Opcode::SetLocal,
+    Opcode::AssignLocal,
+    Opcode::GetFree,
+    Opcode::AssignFree,
+    Opcode::CaptureLocal,
+    Opcode::CaptureFree,
+    Opcode::GetBuiltin,
+    Opcode::Array,
+    Opcode::Hash,
+    Opcode::Index,
+    Opcode::Call,
+    Opcode::ReturnValue,
+    Opcode::Return,
+    Opcode::Closure,
+    Opcode::CurrentClosure,
+];
+
+impl TryFrom<u8> for Opcode {
+    type Error = String;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        return match OPCODES.get(byte as usize) {
+            Some(op) => Ok(*op),
+            None => Err(format!("opcode {} undefined", byte)),
+        };
+    }
+}
+
+impl Display for Opcode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "{}", self.definition().name);
+    }
+}
+
+/// Encodes an instruction
+pub fn make(op: Opcode, operands: &[usize]) -> Instructions {
+    let definition = op.definition();
+
+    let mut instruction = Vec::with_capacity(1 + definition.operand_widths.iter().sum::<usize>());
+    instruction.push(op as u8);
+
+    for (operand, width) in operands.iter().zip(definition.operand_widths) {
+        match width {
+            2 => instruction.extend_from_slice(&(*operand as u16).to_be_bytes()),
+            1 => instruction.push(*operand as u8),
+            _ => unreachable!("There is no operand of width {width}"),
+        }
+    }
+
+    return instruction;
+}
+
+/// Decodes the operands following an opcode, returns them with the number of bytes read
+pub fn read_operands(definition: &Definition, instructions: &[u8]) -> (Vec<usize>, usize) {
+    let mut operands = Vec::with_capacity(definition.operand_widths.len());
+    let mut offset = 0;
+
+    for width in definition.operand_widths {
+        match width {
+            2 => operands.push(read_u16(&instructions[offset..])),
+            1 => operands.push(read_u8(&instructions[offset..])),
+            _ => unreachable!("There is no operand of width {width}"),
+        }
+
+        offset += width;
+    }
+
+    return (operands, offset);
+}
+
+pub fn read_u16(instructions: &[u8]) -> usize {
+    return u16::from_be_bytes([instructions[0], instructions[1]]) as usize;
+}
+
+pub fn read_u8(instructions: &[u8]) -> usize {
+    return instructions[0] as usize;
+}
+
+/// Human readable listing of the instructions, one per line prefixed by its offset
+pub fn disassemble(instructions: &[u8]) -> String {
+    let mut buf = String::new();
+    let mut i = 0;
+
+    while i < instructions.len() {
+        let op = match Opcode::try_from(instructions[i]) {
+            Ok(op) => op,
+            Err(err) => {
+                buf += &format!("ERROR: {}\n", err);
+                i += 1;
+                continue;
+            }
+        };
+
+        let definition = op.definition();
+        let (operands, read) = read_operands(&definition, &instructions[i + 1..]);
+
+        let operands: Vec<String> = operands.iter().map(|o| o.to_string()).collect();
+        if operands.is_empty() {
+            buf += &format!("{:04} {}\n", i, definition.name);
+        } else {
+            buf += &format!("{:04} {} {}\n", i, definition.name, operands.join(" "));
+        }
+
+        i += 1 + read;
+    }
+
+    return buf;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OPCODES, Opcode, disassemble, make, read_operands};
+
+    #[test]
+    fn test_make() {
+        let tests: [(Opcode, &[usize], &[u8]); 4] = [
+            (
+                Opcode::Constant,
+                &[65534],
+                &[Opcode::Constant as u8, 255, 254],
+            ),
+            (Opcode::Add, &[], &[Opcode::Add as u8]),
+            (Opcode::GetLocal, &[255], &[Opcode::GetLocal as u8, 255]),
+            (
+                Opcode::Closure,
+                &[65534, 255],
+                &[Opcode::Closure as u8, 255, 254, 255],
+            ),
+        ];
+
+        for (op, operands, expected) in tests {
+            assert_eq!(make(op, operands), expected, "Wrong encoding for {op}");
+        }
+    }
+
+    #[test]
+    fn test_read_operands() {
+        let tests: [(Opcode, &[usize], usize); 3] = [
+            (Opcode::Constant, &[65535], 2),
+            (Opcode::GetLocal, &[255], 1),
+            (Opcode::Closure, &[65535, 255], 3),
+        ];
+
+        for (op, operands, bytes_read) in tests {
+            let instruction = make(op, operands);
+
+            let (read, n) = read_operands(&op.definition(), &instruction[1..]);
+            assert_eq!(n, bytes_read);
+            assert_eq!(read, operands);
+        }
+    }
+
+    #[test]
+    fn test_opcode_round_trip() {
+        for (byte, op) in OPCODES.iter().enumerate() {
+            assert_eq!(
+                *op as usize, byte,
+                "{op} is not at its discriminant in OPCODES"
+            );
+            assert_eq!(Opcode::try_from(byte as u8), Ok(*op));
+        }
+        assert!(Opcode::try_from(OPCODES.len() as u8).is_err());
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let instructions = [
+            make(Opcode::Add, &[]),
+            make(Opcode::GetLocal, &[1]),
+            make(Opcode::Constant, &[2]),
+            make(Opcode::Constant, &[65535]),
+            make(Opcode::Closure, &[65535, 255]),
+        ]
+        .concat();
+
+        let expected = "0000 OpAdd
+0001 OpGetLocal 1
+0003 OpConstant 2
+0006 OpConstant 65535
+0009 OpClosure 65535 255
+";
+
+        assert_eq!(disassemble(&instructions), expected);
+    }
+}
@@ -0,0 +1,1393 @@
+pub mod symbol_table;
+
+pub use symbol_table::{Symbol, SymbolScope, SymbolTable};
+
+use crate::ast::*;
+use crate::code::{Instructions, Opcode, disassemble, make};
+use crate::evaluator::builtins::BUILTINS;
+use crate::evaluator::{CompiledFunction, Object};
+
+use std::rc::Rc;
+
+/// Output of the compiler, input of the vm
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bytecode {
+    pub instructions: Instructions,
+    pub constants: Vec<Object>,
+    pub num_locals: usize, // variables of the top level loops
+}
+
+impl Bytecode {
+    /// Human readable listing of the instructions followed by the constants
+    /// (compiled functions are listed with their own instructions)
+    pub fn disassemble(&self) -> String {
+        let mut buf = disassemble(&self.instructions);
+        buf += "Constants:";
+
+        for (index, constant) in self.constants.iter().enumerate() {
+            match constant {
+                Object::CompiledFunction(function) => {
+                    buf += &format!("\n{:04} CompiledFunction", index);
+                    for line in disassemble(&function.instructions).lines() {
+                        buf += &format!("\n    {}", line);
+                    }
+                }
+                _ => buf += &format!("\n{:04} {}", index, constant),
+            }
+        }
+
+        return buf;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct EmittedInstruction {
+    opcode: Opcode,
+    position: usize,
+}
+
+/// Instructions of the function being compiled
+#[derive(Debug, Default)]
+struct CompilationScope {
+    instructions: Instructions,
+    last_instruction: Option<EmittedInstruction>,
+    previous_instruction: Option<EmittedInstruction>,
+
+    // loops being compiled, the innermost one last
+    loops: Vec<Loop>,
+}
+
+/// Jump targets of a loop
+#[derive(Debug)]
+struct Loop {
+    start: usize,       // where `continue` jumps to
+    breaks: Vec<usize>, // jumps of the `break`s, patched once the end of the loop is known
+}
+
+pub struct Compiler {
+    constants: Vec<Object>,
+    symbol_table: SymbolTable,
+
+    // the last scope is the one being compiled
+    scopes: Vec<CompilationScope>,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        return Compiler::new();
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        let mut symbol_table = SymbolTable::new();
+        for (index, builtin) in BUILTINS.iter().enumerate() {
+            symbol_table.define_builtin(index, builtin.name);
+        }
+
+        return Compiler::new_with_state(symbol_table, Vec::new());
+    }
+
+    /// Creates a compiler that keeps the globals and constants of a previous compilation
+    pub fn new_with_state(symbol_table: SymbolTable, constants: Vec<Object>) -> Self {
+        return Compiler {
+            constants,
+            symbol_table,
+            scopes: vec![CompilationScope::default()],
+        };
+    }
+
+    /// Gives back the globals and constants so that a later compilation can reuse them
+    pub fn into_state(self) -> (SymbolTable, Vec<Object>) {
+        return (self.symbol_table, self.constants);
+    }
+
+    pub fn bytecode(&self) -> Bytecode {
+        return Bytecode {
+            instructions: self.current_scope().instructions.clone(),
+            constants: self.constants.clone(),
+            num_locals: self.symbol_table.max_locals,
+        };
+    }
+
+    pub fn compile(&mut self, program: &Program) -> Result<(), String> {
+        for statement in &program.statements {
+            self.compile_statement(statement)?;
+        }
+
+        return Ok(());
+    }
+
+    // ======================================================
+    // Statements
+
+    fn compile_statement(&mut self, statement: &Statement) -> Result<(), String> {
+        match statement {
+            Statement::Expression(statement) => {
+                self.compile_expression(&statement.expression)?;
+                self.emit(Opcode::Pop, &[])?;
+            }
+            Statement::Let(statement) => {
+                let name = &statement.identifier.value;
+
+                // a name of the same scope is rebound in place, closures sharing it see the change
+                let existing = self.symbol_table.defined_here(name);
+                let mut assign = existing.is_some();
+
+                let symbol = match &statement.value {
+                    Expression::Function(function) => {
+                        // defined before the body so that functions can refer to themselves
+                        let symbol = match existing {
+                            Some(symbol) => symbol,
+                            None => self.symbol_table.define(name),
+                        };
+                        assign |= self.compile_function(function, Some(name))?;
+                        symbol
+                    }
+                    value => {
+                        self.compile_expression(value)?;
+                        match existing {
+                            Some(symbol) => symbol,
+                            None => self.symbol_table.define(name),
+                        }
+                    }
+                };
+
+                if assign {
+                    self.assign_symbol(&symbol)?;
+                } else {
+                    self.store_symbol(&symbol)?;
+                }
+            }
+            Statement::Return(statement) => {
+                self.compile_expression(&statement.return_value)?;
+                self.emit(Opcode::ReturnValue, &[])?;
+            }
+            Statement::Assign(statement) => self.compile_assign_statement(statement)?,
+            Statement::While(statement) => self.compile_while_statement(statement)?,
+            Statement::For(statement) => self.compile_for_statement(statement)?,
+            Statement::Break(_) => {
+                let jump = self.emit(Opcode::Jump, &[9999])?;
+
+                match self.current_scope_mut().loops.last_mut() {
+                    Some(current_loop) => current_loop.breaks.push(jump),
+                    None => return Err("`break` outside of a loop".to_string()),
+                }
+            }
+            Statement::Continue(_) => match self.current_scope().loops.last() {
+                Some(current_loop) => {
+                    let start = current_loop.start;
+                    self.emit(Opcode::Jump, &[start])?;
+                }
+                None => return Err("`continue` outside of a loop".to_string()),
+            },
+            Statement::Error(statement) => {
+                return Err(format!("invalid statement at {}", statement.span));
+            }
+        }
+
+        return Ok(());
+    }
+
+    fn compile_assign_statement(&mut self, statement: &AssignStatement) -> Result<(), String> {
+        let name = &statement.identifier.value;
+
+        let symbol = match self.symbol_table.resolve_binding(name) {
+            Some(symbol) if symbol.scope == SymbolScope::Builtin => {
+                return Err(format!("cannot assign to builtin: {}", name));
+            }
+            Some(symbol) => symbol,
+            None => return Err(format!("identifier not found: {}", name)),
+        };
+
+        if let Some(operator) = statement.operator() {
+            self.load_symbol(&symbol)?;
+            self.compile_expression(&statement.value)?;
+            self.emit_infix_operator(operator)?;
+        } else {
+            self.compile_expression(&statement.value)?;
+        }
+
+        self.assign_symbol(&symbol)?;
+
+        return Ok(());
+    }
+
+    fn compile_while_statement(&mut self, statement: &WhileStatement) -> Result<(), String> {
+        let start = self.current_scope().instructions.len();
+
+        self.compile_expression(&statement.condition)?;
+        let jump_not_truthy = self.emit(Opcode::JumpNotTruthy, &[9999])?;
+
+        self.compile_loop_body(&statement.body, None, start)?;
+
+        let after_loop = self.current_scope().instructions.len();
+        self.change_operand(jump_not_truthy, after_loop)?;
+
+        return Ok(());
+    }
+
+    /// The array and the position in it are kept in hidden locals (their names are not
+    /// identifiers), OpIterNext pushes the next element or jumps past the loop
+    fn compile_for_statement(&mut self, statement: &ForStatement) -> Result<(), String> {
+        self.symbol_table.enter_block();
+        let result = self.compile_for_loop(statement);
+        self.symbol_table.leave_block();
+
+        return result;
+    }
+
+    fn compile_for_loop(&mut self, statement: &ForStatement) -> Result<(), String> {
+        let iterable = self.symbol_table.define("@iterable");
+        let index = self.symbol_table.define("@index");
+
+        self.compile_expression(&statement.iterable)?;
+        self.store_symbol(&iterable)?;
+
+        let zero = self.add_constant(Object::Integer(0));
+        self.emit(Opcode::Constant, &[zero])?;
+        self.store_symbol(&index)?;
+
+        let start = self.current_scope().instructions.len();
+
+        self.load_symbol(&iterable)?;
+        self.load_symbol(&index)?;
+        let iter_next = self.emit(Opcode::IterNext, &[9999])?;
+
+        // the element stays on the stack for the body to bind
+        self.load_symbol(&index)?;
+        let one = self.add_constant(Object::Integer(1));
+        self.emit(Opcode::Constant, &[one])?;
+        self.emit(Opcode::Add, &[])?;
+        self.store_symbol(&index)?;
+
+        self.compile_loop_body(&statement.body, Some(&statement.variable), start)?;
+
+        let after_loop = self.current_scope().instructions.len();
+        self.change_operand(iter_next, after_loop)?;
+
+        return Ok(());
+    }
+
+    /// Compiles the body followed by the jump back to `start`, the `break`s land after it
+    /// Each iteration has its own variables: the ones of the body (and `variable`, bound to
+    /// the value on top of the stack) are locals of a block
+    fn compile_loop_body(
+        &mut self,
+        body: &BlockStatement,
+        variable: Option<&Identifier>,
+        start: usize,
+    ) -> Result<(), String> {
+        self.current_scope_mut().loops.push(Loop {
+            start,
+            breaks: Vec::new(),
+        });
+        self.symbol_table.enter_block();
+
+        let result = self.compile_iteration(body, variable);
+
+        self.symbol_table.leave_block();
+        let current_loop = self
+            .current_scope_mut()
+            .loops
+            .pop()
+            .expect("The loop was pushed above");
+        result?;
+
+        self.emit(Opcode::Jump, &[start])?;
+
+        let after_loop = self.current_scope().instructions.len();
+        for jump in current_loop.breaks {
+            self.change_operand(jump, after_loop)?;
+        }
+
+        return Ok(());
+    }
+
+    fn compile_iteration(
+        &mut self,
+        body: &BlockStatement,
+        variable: Option<&Identifier>,
+    ) -> Result<(), String> {
+        if let Some(variable) = variable {
+            let symbol = self.symbol_table.define(&variable.value);
+            self.store_symbol(&symbol)?;
+        }
+
+        return self.compile_block_statement(body);
+    }
+
+    fn compile_block_statement(&mut self, block: &BlockStatement) -> Result<(), String> {
+        for statement in &block.statements {
+            self.compile_statement(statement)?;
+        }
+
+        return Ok(());
+    }
+
+    // ======================================================
+    // Expressions
+
+    fn compile_expression(&mut self, expression: &Expression) -> Result<(), String> {
+        match expression {
+            Expression::Integer(integer) => {
+                let constant = self.add_constant(Object::Integer(integer.value));
+                self.emit(Opcode::Constant, &[constant])?;
+            }
+            Expression::Float(float) => {
+                let constant = self.add_constant(Object::Float(float.value));
+                self.emit(Opcode::Constant, &[constant])?;
+            }
+            Expression::String(string) => {
+                let constant = self.add_constant(Object::String(string.value.clone()));
+                self.emit(Opcode::Constant, &[constant])?;
+            }
+            Expression::Boolean(boolean) => {
+                if boolean.value {
+                    self.emit(Opcode::True, &[])?;
+                } else {
+                    self.emit(Opcode::False, &[])?;
+                }
+            }
+            Expression::Identifier(identifier) => {
+                let symbol = match self.symbol_table.resolve(&identifier.value) {
+                    Some(symbol) => symbol,
+                    None => return Err(format!("identifier not found: {}", identifier.value)),
+                };
+
+                self.load_symbol(&symbol)?;
+            }
+            Expression::Prefix(prefix) => {
+                self.compile_expression(&prefix.right)?;
+
+                match prefix.operator.as_str() {
+                    "!" => self.emit(Opcode::Bang, &[])?,
+                    "-" => self.emit(Opcode::Minus, &[])?,
+                    operator => return Err(format!("unknown operator: {}", operator)),
+                };
+            }
+            Expression::Infix(infix) if infix.operator == "&&" || infix.operator == "||" => {
+                self.compile_logical_expression(infix)?;
+            }
+            Expression::Infix(infix) => {
+                self.compile_expression(&infix.left)?;
+                self.compile_expression(&infix.right)?;
+                self.emit_infix_operator(&infix.operator)?;
+            }
+            Expression::If(if_expression) => self.compile_if_expression(if_expression)?,
+            Expression::Function(function) => {
+                self.compile_function(function, None)?;
+            }
+            Expression::Call(call) => {
+                self.compile_expression(&call.function)?;
+
+                for argument in &call.arguments {
+                    self.compile_expression(argument)?;
+                }
+
+                self.emit(Opcode::Call, &[call.arguments.len()])?;
+            }
+            Expression::Array(array) => {
+                for element in &array.elements {
+                    self.compile_expression(element)?;
+                }
+
+                self.emit(Opcode::Array, &[array.elements.len()])?;
+            }
+            Expression::Index(index) => {
+                self.compile_expression(&index.left)?;
+                self.compile_expression(&index.index)?;
+
+                self.emit(Opcode::Index, &[])?;
+            }
+            Expression::Hash(hash) => {
+                for (key, value) in &hash.pairs {
+                    self.compile_expression(key)?;
+                    self.compile_expression(value)?;
+                }
+
+                self.emit(Opcode::Hash, &[hash.pairs.len() * 2])?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    fn emit_infix_operator(&mut self, operator: &str) -> Result<(), String> {
+        match operator {
+            "+" => self.emit(Opcode::Add, &[]),
+            "-" => self.emit(Opcode::Sub, &[]),
+            "*" => self.emit(Opcode::Mul, &[]),
+            "/" => self.emit(Opcode::Div, &[]),
+            "%" => self.emit(Opcode::Mod, &[]),
+            ">" => self.emit(Opcode::GreaterThan, &[]),
+            "<" => self.emit(Opcode::LessThan, &[]),
+            ">=" => self.emit(Opcode::GreaterEqual, &[]),
+            "<=" => self.emit(Opcode::LessEqual, &[]),
+            "==" => self.emit(Opcode::Equal, &[]),
+            "!=" => self.emit(Opcode::NotEqual, &[]),
+            operator => return Err(format!("unknown operator: {}", operator)),
+        }?;
+
+        return Ok(());
+    }
+
+    /// Short-circuiting `&&` and `||`, the operand that decides the result is turned into a
+    /// boolean with a double OpBang
+    fn compile_logical_expression(&mut self, infix: &InfixExpression) -> Result<(), String> {
+        self.compile_expression(&infix.left)?;
+
+        // the jump targets are patched once the operands are compiled
+        let jump_not_truthy = self.emit(Opcode::JumpNotTruthy, &[9999])?;
+
+        if infix.operator == "&&" {
+            self.compile_expression(&infix.right)?;
+            self.emit(Opcode::Bang, &[])?;
+            self.emit(Opcode::Bang, &[])?;
+        } else {
+            self.emit(Opcode::True, &[])?;
+        }
+
+        let jump = self.emit(Opcode::Jump, &[9999])?;
+
+        let after_truthy = self.current_scope().instructions.len();
+        self.change_operand(jump_not_truthy, after_truthy)?;
+
+        if infix.operator == "&&" {
+            self.emit(Opcode::False, &[])?;
+        } else {
+            self.compile_expression(&infix.right)?;
+            self.emit(Opcode::Bang, &[])?;
+            self.emit(Opcode::Bang, &[])?;
+        }
+
+        let after_falsy = self.current_scope().instructions.len();
+        self.change_operand(jump, after_falsy)?;
+
+        return Ok(());
+    }
+
+    fn compile_if_expression(&mut self, if_expression: &IfExpression) -> Result<(), String> {
+        self.compile_expression(&if_expression.condition)?;
+
+        // the jump targets are patched once the branches are compiled
+        let jump_not_truthy = self.emit(Opcode::JumpNotTruthy, &[9999])?;
+
+        self.compile_block_statement(&if_expression.consequence)?;
+        self.remove_last_pop_or_push_null()?;
+
+        let jump = self.emit(Opcode::Jump, &[9999])?;
+
+        let after_consequence = self.current_scope().instructions.len();
+        self.change_operand(jump_not_truthy, after_consequence)?;
+
+        match &if_expression.alternative {
+            Some(alternative) => {
+                self.compile_block_statement(alternative)?;
+                self.remove_last_pop_or_push_null()?;
+            }
+            None => {
+                self.emit(Opcode::Null, &[])?;
+            }
+        }
+
+        let after_alternative = self.current_scope().instructions.len();
+        self.change_operand(jump, after_alternative)?;
+
+        return Ok(());
+    }
+
+    /// Compiles the function into a constant and emits the closure creation
+    /// `name` is the name the function is bound to (if any) so that it can call itself,
+    /// returns whether the closure captured that variable (the function assigns its own name)
+    fn compile_function(
+        &mut self,
+        function: &FunctionLiteral,
+        name: Option<&str>,
+    ) -> Result<bool, String> {
+        self.enter_scope();
+
+        if let Some(name) = name {
+            self.symbol_table.define_function_name(name);
+        }
+
+        for parameter in &function.parameters {
+            self.symbol_table.define(&parameter.value);
+        }
+
+        self.compile_block_statement(&function.body)?;
+
+        // the last expression of the body is the implicit return value
+        if self.last_instruction_is(Opcode::Pop) {
+            let position = self.current_scope().last_instruction.unwrap().position;
+            self.replace_instruction(position, make(Opcode::ReturnValue, &[]));
+            self.current_scope_mut().last_instruction = Some(EmittedInstruction {
+                opcode: Opcode::ReturnValue,
+                position,
+            });
+        }
+        if !self.last_instruction_is(Opcode::ReturnValue) {
+            self.emit(Opcode::Return, &[])?;
+        }
+
+        let free_symbols = self.symbol_table.free_symbols.clone();
+        let num_locals = self.symbol_table.max_locals;
+        let instructions = self.leave_scope();
+
+        // the variable being bound is captured before the `let` stores into it: it is cleared
+        // so that the closure does not share what the slot held before
+        let captured_binding = name
+            .and_then(|name| self.symbol_table.defined_here(name))
+            .filter(|binding| {
+                binding.scope == SymbolScope::Local && free_symbols.contains(binding)
+            });
+        if let Some(binding) = &captured_binding {
+            self.emit(Opcode::Null, &[])?;
+            self.store_symbol(binding)?;
+        }
+
+        // the captured variables are pushed on the stack for the closure to collect
+        for symbol in &free_symbols {
+            self.capture_symbol(symbol)?;
+        }
+
+        let compiled_function = Object::CompiledFunction(Rc::new(CompiledFunction {
+            instructions,
+            num_locals,
+            num_parameters: function.parameters.len(),
+        }));
+
+        let constant = self.add_constant(compiled_function);
+        self.emit(Opcode::Closure, &[constant, free_symbols.len()])?;
+
+        return Ok(captured_binding.is_some());
+    }
+
+    fn load_symbol(&mut self, symbol: &Symbol) -> Result<(), String> {
+        match symbol.scope {
+            SymbolScope::Global => self.emit(Opcode::GetGlobal, &[symbol.index]),
+            SymbolScope::Local => self.emit(Opcode::GetLocal, &[symbol.index]),
+            SymbolScope::Free => self.emit(Opcode::GetFree, &[symbol.index]),
+            SymbolScope::Function => self.emit(Opcode::CurrentClosure, &[]),
+            SymbolScope::Builtin => self.emit(Opcode::GetBuiltin, &[symbol.index]),
+        }?;
+
+        return Ok(());
+    }
+
+    /// Binds a new variable (`let`)
+    fn store_symbol(&mut self, symbol: &Symbol) -> Result<(), String> {
+        match symbol.scope {
+            SymbolScope::Global => self.emit(Opcode::SetGlobal, &[symbol.index]),
+            _ => self.emit(Opcode::SetLocal, &[symbol.index]),
+        }?;
+
+        return Ok(());
+    }
+
+    /// Writes to an existing variable, as seen by the closures sharing it
+    fn assign_symbol(&mut self, symbol: &Symbol) -> Result<(), String> {
+        match symbol.scope {
+            SymbolScope::Global => self.emit(Opcode::SetGlobal, &[symbol.index]),
+            SymbolScope::Local => self.emit(Opcode::AssignLocal, &[symbol.index]),
+            SymbolScope::Free => self.emit(Opcode::AssignFree, &[symbol.index]),
+            _ => unreachable!("Only variables can be assigned"),
+        }?;
+
+        return Ok(());
+    }
+
+    /// Pushes a variable of this scope for a closure to share
+    fn capture_symbol(&mut self, symbol: &Symbol) -> Result<(), String> {
+        match symbol.scope {
+            SymbolScope::Local => self.emit(Opcode::CaptureLocal, &[symbol.index]),
+            SymbolScope::Free => self.emit(Opcode::CaptureFree, &[symbol.index]),
+            SymbolScope::Function => self.emit(Opcode::CurrentClosure, &[]),
+            _ => unreachable!("Globals and builtins are not captured"),
+        }?;
+
+        return Ok(());
+    }
+
+    // ======================================================
+    // Scopes
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(CompilationScope::default());
+
+        let outer = std::mem::take(&mut self.symbol_table);
+        self.symbol_table = SymbolTable::new_enclosed(outer);
+    }
+
+    /// Returns the instructions of the scope that was left
+    fn leave_scope(&mut self) -> Instructions {
+        let scope = self.scopes.pop().expect("There is always a scope to leave");
+
+        let outer = self.symbol_table.outer.take();
+        self.symbol_table = *outer.expect("A local symbol table always has an outer table");
+
+        return scope.instructions;
+    }
+
+    fn current_scope(&self) -> &CompilationScope {
+        return self.scopes.last().expect("There is always a scope");
+    }
+
+    fn current_scope_mut(&mut self) -> &mut CompilationScope {
+        return self.scopes.last_mut().expect("There is always a scope");
+    }
+
+    // ======================================================
+    // Instructions helpers
+
+    fn add_constant(&mut self, object: Object) -> usize {
+        self.constants.push(object);
+        return self.constants.len() - 1;
+    }
+
+    /// Emits an instruction and returns its position
+    /// (fails when an operand does not fit in its width)
+    fn emit(&mut self, op: Opcode, operands: &[usize]) -> Result<usize, String> {
+        check_operands(op, operands)?;
+        let instruction = make(op, operands);
+
+        let scope = self.current_scope_mut();
+        let position = scope.instructions.len();
+        scope.instructions.extend(instruction);
+
+        scope.previous_instruction = scope.last_instruction;
+        scope.last_instruction = Some(EmittedInstruction {
+            opcode: op,
+            position,
+        });
+
+        return Ok(position);
+    }
+
+    fn last_instruction_is(&self, op: Opcode) -> bool {
+        return match self.current_scope().last_instruction {
+            Some(last) => last.opcode == op,
+            None => false,
+        };
+    }
+
+    /// Keeps the value of the last expression of a branch on the stack
+    fn remove_last_pop_or_push_null(&mut self) -> Result<(), String> {
+        if self.last_instruction_is(Opcode::Pop) {
+            let scope = self.current_scope_mut();
+            let last = scope.last_instruction.unwrap();
+
+            scope.instructions.truncate(last.position);
+            scope.last_instruction = scope.previous_instruction;
+        } else {
+            // empty branch
+            self.emit(Opcode::Null, &[])?;
+        }
+
+        return Ok(());
+    }
+
+    fn replace_instruction(&mut self, position: usize, instruction: Instructions) {
+        let scope = self.current_scope_mut();
+        scope.instructions[position..position + instruction.len()].copy_from_slice(&instruction);
+    }
+
+    fn change_operand(&mut self, position: usize, operand: usize) -> Result<(), String> {
+        let op = Opcode::try_from(self.current_scope().instructions[position])
+            .expect("Only emitted instructions can be changed");
+
+        check_operands(op, &[operand])?;
+        self.replace_instruction(position, make(op, &[operand]));
+
+        return Ok(());
+    }
+}
+
+/// Error when an operand is too big for its width in the encoded instruction
+/// (ex: the 65537th global can not be addressed by OpSetGlobal)
+fn check_operands(op: Opcode, operands: &[usize]) -> Result<(), String> {
+    for (i, (operand, width)) in operands
+        .iter()
+        .zip(op.definition().operand_widths)
+        .enumerate()
+    {
+        let limit = 1 << (8 * width);
+        if *operand < limit {
+            continue;
+        }
+
+        // indexes go up to limit - 1, so there can be `limit` things, counts can only be limit - 1
+        let (what, most) = match (op, i) {
+            (Opcode::Constant, _) | (Opcode::Closure, 0) => ("constants", limit),
+            (Opcode::GetGlobal | Opcode::SetGlobal, _) => ("global variables", limit),
+            (
+                Opcode::GetLocal | Opcode::SetLocal | Opcode::AssignLocal | Opcode::CaptureLocal,
+                _,
+            ) => ("local variables", limit),
+            (Opcode::GetFree | Opcode::AssignFree | Opcode::CaptureFree, _) => {
+                ("free variables", limit)
+            }
+            (Opcode::Closure, _) => ("free variables", limit - 1),
+            (Opcode::Call, _) => ("arguments", limit - 1),
+            (Opcode::Array, _) => ("elements in an array", limit - 1),
+            (Opcode::Hash, _) => ("keys and values in a hash", limit - 1),
+            _ => ("instructions", limit),
+        };
+
+        return Err(format!("too many {} (at most {})", what, most));
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CompiledFunction, Compiler, Object};
+    use crate::code::{Instructions, Opcode, disassemble, make};
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    use std::rc::Rc;
+
+    #[test]
+    fn test_integer_arithmetic() {
+        let tests = [
+            (
+                "1 + 2",
+                vec![Object::Integer(1), Object::Integer(2)],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Add, &[]),
+                    make(Opcode::Pop, &[]),
+                ],
+            ),
+            (
+                "1; 2",
+                vec![Object::Integer(1), Object::Integer(2)],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Pop, &[]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Pop, &[]),
+                ],
+            ),
+            (
+                "-1 * 2 < 3",
+                vec![Object::Integer(1), Object::Integer(2), Object::Integer(3)],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Minus, &[]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Mul, &[]),
+                    make(Opcode::Constant, &[2]),
+                    make(Opcode::LessThan, &[]),
+                    make(Opcode::Pop, &[]),
+                ],
+            ),
+            (
+                "!true == false",
+                vec![],
+                vec![
+                    make(Opcode::True, &[]),
+                    make(Opcode::Bang, &[]),
+                    make(Opcode::False, &[]),
+                    make(Opcode::Equal, &[]),
+                    make(Opcode::Pop, &[]),
+                ],
+            ),
+            (
+                "1.5 * 2",
+                vec![Object::Float(1.5), Object::Integer(2)],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Mul, &[]),
+                    make(Opcode::Pop, &[]),
+                ],
+            ),
+            (
+                "1 % 2 <= 3 >= 4",
+                vec![
+                    Object::Integer(1),
+                    Object::Integer(2),
+                    Object::Integer(3),
+                    Object::Integer(4),
+                ],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Mod, &[]),
+                    make(Opcode::Constant, &[2]),
+                    make(Opcode::LessEqual, &[]),
+                    make(Opcode::Constant, &[3]),
+                    make(Opcode::GreaterEqual, &[]),
+                    make(Opcode::Pop, &[]),
+                ],
+            ),
+        ];
+
+        run_compiler_tests(&tests);
+    }
+
+    #[test]
+    fn test_conditionals() {
+        let tests = [
+            (
+                "if (true) { 10 }; 3333;",
+                vec![Object::Integer(10), Object::Integer(3333)],
+                vec![
+                    make(Opcode::True, &[]),            // 0000
+                    make(Opcode::JumpNotTruthy, &[10]), // 0001
+                    make(Opcode::Constant, &[0]),       // 0004
+                    make(Opcode::Jump, &[11]),          // 0007
+                    make(Opcode::Null, &[]),            // 0010
+                    make(Opcode::Pop, &[]),             // 0011
+                    make(Opcode::Constant, &[1]),       // 0012
+                    make(Opcode::Pop, &[]),             // 0015
+                ],
+            ),
+            (
+                "if (true) { 10 } else { 20 }; 3333;",
+                vec![
+                    Object::Integer(10),
+                    Object::Integer(20),
+                    Object::Integer(3333),
+                ],
+                vec![
+                    make(Opcode::True, &[]),            // 0000
+                    make(Opcode::JumpNotTruthy, &[10]), // 0001
+                    make(Opcode::Constant, &[0]),       // 0004
+                    make(Opcode::Jump, &[13]),          // 0007
+                    make(Opcode::Constant, &[1]),       // 0010
+                    make(Opcode::Pop, &[]),             // 0013
+                    make(Opcode::Constant, &[2]),       // 0014
+                    make(Opcode::Pop, &[]),             // 0017
+                ],
+            ),
+        ];
+
+        run_compiler_tests(&tests);
+    }
+
+    #[test]
+    fn test_logical_expressions() {
+        let tests = [
+            (
+                "true && false",
+                vec![],
+                vec![
+                    make(Opcode::True, &[]),            // 0000
+                    make(Opcode::JumpNotTruthy, &[10]), // 0001
+                    make(Opcode::False, &[]),           // 0004
+                    make(Opcode::Bang, &[]),            // 0005
+                    make(Opcode::Bang, &[]),            // 0006
+                    make(Opcode::Jump, &[11]),          // 0007
+                    make(Opcode::False, &[]),           // 0010
+                    make(Opcode::Pop, &[]),             // 0011
+                ],
+            ),
+            (
+                "false || true",
+                vec![],
+                vec![
+                    make(Opcode::False, &[]),          // 0000
+                    make(Opcode::JumpNotTruthy, &[8]), // 0001
+                    make(Opcode::True, &[]),           // 0004
+                    make(Opcode::Jump, &[11]),         // 0005
+                    make(Opcode::True, &[]),           // 0008
+                    make(Opcode::Bang, &[]),           // 0009
+                    make(Opcode::Bang, &[]),           // 0010
+                    make(Opcode::Pop, &[]),            // 0011
+                ],
+            ),
+        ];
+
+        run_compiler_tests(&tests);
+    }
+
+    #[test]
+    fn test_assign_statements() {
+        let tests = [
+            (
+                "let x = 1; x += 2;",
+                vec![Object::Integer(1), Object::Integer(2)],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::SetGlobal, &[0]),
+                    make(Opcode::GetGlobal, &[0]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Add, &[]),
+                    make(Opcode::SetGlobal, &[0]),
+                ],
+            ),
+            (
+                "fn(a) { a *= 2 }",
+                vec![
+                    Object::Integer(2),
+                    compiled_function(
+                        vec![
+                            make(Opcode::GetLocal, &[0]),
+                            make(Opcode::Constant, &[0]),
+                            make(Opcode::Mul, &[]),
+                            make(Opcode::AssignLocal, &[0]),
+                            make(Opcode::Return, &[]),
+                        ],
+                        1,
+                        1,
+                    ),
+                ],
+                vec![make(Opcode::Closure, &[1, 0]), make(Opcode::Pop, &[])],
+            ),
+        ];
+
+        run_compiler_tests(&tests);
+    }
+
+    #[test]
+    fn test_loops() {
+        let tests = [
+            (
+                "while (true) { break; continue; }",
+                vec![],
+                vec![
+                    make(Opcode::True, &[]),            // 0000
+                    make(Opcode::JumpNotTruthy, &[13]), // 0001
+                    make(Opcode::Jump, &[13]),          // 0004
+                    make(Opcode::Jump, &[0]),           // 0007
+                    make(Opcode::Jump, &[0]),           // 0010
+                ],
+            ),
+            (
+                "for (x in [1]) { x }",
+                vec![Object::Integer(1), Object::Integer(0), Object::Integer(1)],
+                vec![
+                    make(Opcode::Constant, &[0]),  // 0000
+                    make(Opcode::Array, &[1]),     // 0003
+                    make(Opcode::SetLocal, &[0]),  // 0006
+                    make(Opcode::Constant, &[1]),  // 0008
+                    make(Opcode::SetLocal, &[1]),  // 0011
+                    make(Opcode::GetLocal, &[0]),  // 0013
+                    make(Opcode::GetLocal, &[1]),  // 0015
+                    make(Opcode::IterNext, &[36]), // 0017
+                    make(Opcode::GetLocal, &[1]),  // 0020
+                    make(Opcode::Constant, &[2]),  // 0022
+                    make(Opcode::Add, &[]),        // 0025
+                    make(Opcode::SetLocal, &[1]),  // 0026
+                    make(Opcode::SetLocal, &[2]),  // 0028
+                    make(Opcode::GetLocal, &[2]),  // 0030
+                    make(Opcode::Pop, &[]),        // 0032
+                    make(Opcode::Jump, &[13]),     // 0033
+                ],
+            ),
+        ];
+
+        run_compiler_tests(&tests);
+    }
+
+    #[test]
+    fn test_global_let_statements() {
+        let tests = [(
+            "let one = 1; let two = one; two;",
+            vec![Object::Integer(1)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::SetGlobal, &[1]),
+                make(Opcode::GetGlobal, &[1]),
+                make(Opcode::Pop, &[]),
+            ],
+        )];
+
+        run_compiler_tests(&tests);
+    }
+
+    #[test]
+    fn test_string_expressions() {
+        let tests = [
+            (
+                r#""monkey""#,
+                vec![Object::String("monkey".to_string())],
+                vec![make(Opcode::Constant, &[0]), make(Opcode::Pop, &[])],
+            ),
+            (
+                r#""mon" + "key""#,
+                vec![
+                    Object::String("mon".to_string()),
+                    Object::String("key".to_string()),
+                ],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Add, &[]),
+                    make(Opcode::Pop, &[]),
+                ],
+            ),
+        ];
+
+        run_compiler_tests(&tests);
+    }
+
+    #[test]
+    fn test_array_literals() {
+        let tests = [
+            (
+                "[]",
+                vec![],
+                vec![make(Opcode::Array, &[0]), make(Opcode::Pop, &[])],
+            ),
+            (
+                "[1, 2][1]",
+                vec![Object::Integer(1), Object::Integer(2), Object::Integer(1)],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Array, &[2]),
+                    make(Opcode::Constant, &[2]),
+                    make(Opcode::Index, &[]),
+                    make(Opcode::Pop, &[]),
+                ],
+            ),
+        ];
+
+        run_compiler_tests(&tests);
+    }
+
+    #[test]
+    fn test_hash_literals() {
+        let tests = [
+            (
+                "{}",
+                vec![],
+                vec![make(Opcode::Hash, &[0]), make(Opcode::Pop, &[])],
+            ),
+            (
+                "{1: 2, 3: 4 * 5}[1]",
+                vec![
+                    Object::Integer(1),
+                    Object::Integer(2),
+                    Object::Integer(3),
+                    Object::Integer(4),
+                    Object::Integer(5),
+                    Object::Integer(1),
+                ],
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Constant, &[2]),
+                    make(Opcode::Constant, &[3]),
+                    make(Opcode::Constant, &[4]),
+                    make(Opcode::Mul, &[]),
+                    make(Opcode::Hash, &[4]),
+                    make(Opcode::Constant, &[5]),
+                    make(Opcode::Index, &[]),
+                    make(Opcode::Pop, &[]),
+                ],
+            ),
+        ];
+
+        run_compiler_tests(&tests);
+    }
+
+    #[test]
+    fn test_builtins() {
+        let tests = [
+            (
+                "len([]); push([], 1);",
+                vec![Object::Integer(1)],
+                vec![
+                    make(Opcode::GetBuiltin, &[0]),
+                    make(Opcode::Array, &[0]),
+                    make(Opcode::Call, &[1]),
+                    make(Opcode::Pop, &[]),
+                    make(Opcode::GetBuiltin, &[4]),
+                    make(Opcode::Array, &[0]),
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Call, &[2]),
+                    make(Opcode::Pop, &[]),
+                ],
+            ),
+            (
+                "fn() { len([]) }",
+                vec![compiled_function(
+                    vec![
+                        make(Opcode::GetBuiltin, &[0]),
+                        make(Opcode::Array, &[0]),
+                        make(Opcode::Call, &[1]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    0,
+                    0,
+                )],
+                vec![make(Opcode::Closure, &[0, 0]), make(Opcode::Pop, &[])],
+            ),
+        ];
+
+        run_compiler_tests(&tests);
+    }
+
+    #[test]
+    fn test_functions() {
+        let tests = [
+            (
+                "fn() { return 5 + 10 }",
+                vec![
+                    Object::Integer(5),
+                    Object::Integer(10),
+                    compiled_function(
+                        vec![
+                            make(Opcode::Constant, &[0]),
+                            make(Opcode::Constant, &[1]),
+                            make(Opcode::Add, &[]),
+                            make(Opcode::ReturnValue, &[]),
+                        ],
+                        0,
+                        0,
+                    ),
+                ],
+                vec![make(Opcode::Closure, &[2, 0]), make(Opcode::Pop, &[])],
+            ),
+            (
+                "fn() { 1; 2 }",
+                vec![
+                    Object::Integer(1),
+                    Object::Integer(2),
+                    compiled_function(
+                        vec![
+                            make(Opcode::Constant, &[0]),
+                            make(Opcode::Pop, &[]),
+                            make(Opcode::Constant, &[1]),
+                            make(Opcode::ReturnValue, &[]),
+                        ],
+                        0,
+                        0,
+                    ),
+                ],
+                vec![make(Opcode::Closure, &[2, 0]), make(Opcode::Pop, &[])],
+            ),
+            (
+                "fn() { }",
+                vec![compiled_function(vec![make(Opcode::Return, &[])], 0, 0)],
+                vec![make(Opcode::Closure, &[0, 0]), make(Opcode::Pop, &[])],
+            ),
+            (
+                "let one_arg = fn(a) { a }; one_arg(24);",
+                vec![
+                    compiled_function(
+                        vec![make(Opcode::GetLocal, &[0]), make(Opcode::ReturnValue, &[])],
+                        1,
+                        1,
+                    ),
+                    Object::Integer(24),
+                ],
+                vec![
+                    make(Opcode::Closure, &[0, 0]),
+                    make(Opcode::SetGlobal, &[0]),
+                    make(Opcode::GetGlobal, &[0]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Call, &[1]),
+                    make(Opcode::Pop, &[]),
+                ],
+            ),
+        ];
+
+        run_compiler_tests(&tests);
+    }
+
+    #[test]
+    fn test_closures() {
+        let tests = [
+            (
+                "fn(a) { fn(b) { a + b } }",
+                vec![
+                    compiled_function(
+                        vec![
+                            make(Opcode::GetFree, &[0]),
+                            make(Opcode::GetLocal, &[0]),
+                            make(Opcode::Add, &[]),
+                            make(Opcode::ReturnValue, &[]),
+                        ],
+                        1,
+                        1,
+                    ),
+                    compiled_function(
+                        vec![
+                            make(Opcode::CaptureLocal, &[0]),
+                            make(Opcode::Closure, &[0, 1]),
+                            make(Opcode::ReturnValue, &[]),
+                        ],
+                        1,
+                        1,
+                    ),
+                ],
+                vec![make(Opcode::Closure, &[1, 0]), make(Opcode::Pop, &[])],
+            ),
+            (
+                "fn(a) { fn() { a = 1 } }",
+                vec![
+                    Object::Integer(1),
+                    compiled_function(
+                        vec![
+                            make(Opcode::Constant, &[0]),
+                            make(Opcode::AssignFree, &[0]),
+                            make(Opcode::Return, &[]),
+                        ],
+                        0,
+                        0,
+                    ),
+                    compiled_function(
+                        vec![
+                            make(Opcode::CaptureLocal, &[0]),
+                            make(Opcode::Closure, &[1, 1]),
+                            make(Opcode::ReturnValue, &[]),
+                        ],
+                        1,
+                        1,
+                    ),
+                ],
+                vec![make(Opcode::Closure, &[2, 0]), make(Opcode::Pop, &[])],
+            ),
+        ];
+
+        run_compiler_tests(&tests);
+    }
+
+    #[test]
+    fn test_recursive_functions() {
+        let tests = [(
+            "let wrapper = fn() { let countdown = fn(x) { countdown(x - 1); }; countdown(1); };",
+            vec![
+                Object::Integer(1),
+                compiled_function(
+                    vec![
+                        make(Opcode::CurrentClosure, &[]),
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::Constant, &[0]),
+                        make(Opcode::Sub, &[]),
+                        make(Opcode::Call, &[1]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    1,
+                    1,
+                ),
+                Object::Integer(1),
+                compiled_function(
+                    vec![
+                        make(Opcode::Closure, &[1, 0]),
+                        make(Opcode::SetLocal, &[0]),
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::Constant, &[2]),
+                        make(Opcode::Call, &[1]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    1,
+                    0,
+                ),
+            ],
+            vec![
+                make(Opcode::Closure, &[3, 0]),
+                make(Opcode::SetGlobal, &[0]),
+            ],
+        )];
+
+        run_compiler_tests(&tests);
+    }
+
+    #[test]
+    fn test_undefined_identifier() {
+        let mut lexer = Lexer::new("let a = b;");
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program();
+
+        let mut compiler = Compiler::new();
+        assert_eq!(
+            compiler.compile(&program),
+            Err("identifier not found: b".to_string())
+        );
+    }
+
+    #[test]
+    fn test_operand_limits() {
+        let lets = |count: usize, value: &str| -> String {
+            return (0..count)
+                .map(|i| format!("let x{} = {};", i, value))
+                .collect();
+        };
+
+        let tests = [
+            (lets(65536, "true"), None),
+            (
+                lets(65537, "true"),
+                Some("too many global variables (at most 65536)"),
+            ),
+            (
+                "1;".repeat(65537),
+                Some("too many constants (at most 65536)"),
+            ),
+            (format!("fn() {{ {} }}", lets(256, "true")), None),
+            (
+                format!("fn() {{ {} }}", lets(257, "true")),
+                Some("too many local variables (at most 256)"),
+            ),
+            (
+                format!("fn(f) {{ f({}) }}", vec!["1"; 255].join(", ")),
+                None,
+            ),
+            (
+                format!("fn(f) {{ f({}) }}", vec!["1"; 256].join(", ")),
+                Some("too many arguments (at most 255)"),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(&input);
+            let mut parser = Parser::new(&mut lexer);
+            let program = parser.parse_program();
+
+            let mut compiler = Compiler::new();
+            assert_eq!(
+                compiler.compile(&program).err().as_deref(),
+                expected,
+                "Wrong result for {} bytes of input",
+                input.len()
+            );
+        }
+    }
+
+    // ===========================================
+    // HELPERS
+    fn compiled_function(
+        instructions: Vec<Instructions>,
+        num_locals: usize,
+        num_parameters: usize,
+    ) -> Object {
+        return Object::CompiledFunction(Rc::new(CompiledFunction {
+            instructions: instructions.concat(),
+            num_locals,
+            num_parameters,
+        }));
+    }
+
+    fn run_compiler_tests(tests: &[(&str, Vec<Object>, Vec<Instructions>)]) {
+        for (input, expected_constants, expected_instructions) in tests {
+            let mut lexer = Lexer::new(input);
+            let mut parser = Parser::new(&mut lexer);
+            let program = parser.parse_program();
+
+            let mut compiler = Compiler::new();
+            compiler.compile(&program).unwrap();
+
+            let bytecode = compiler.bytecode();
+            assert_eq!(
+                disassemble(&bytecode.instructions),
+                disassemble(&expected_instructions.concat()),
+                "Wrong instructions for `{input}`"
+            );
+            assert_eq!(
+                &bytecode.constants, expected_constants,
+                "Wrong constants for `{input}`"
+            );
+        }
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum SymbolScope {
+    Global,
+    Local,
+    Free,     // local of an enclosing function captured by a closure
+    Function, // the function currently being defined (for recursion)
+    Builtin,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub scope: SymbolScope,
+    pub index: usize,
+}
+
+/// Resolves names to the slots they are stored in
+#[derive(Debug, Default, Clone)]
+pub struct SymbolTable {
+    pub outer: Option<Box<SymbolTable>>,
+
+    store: HashMap<String, Symbol>,
+    num_globals: usize,
+
+    // blocks being compiled (ex: a loop body), the innermost one last
+    blocks: Vec<Block>,
+
+    num_locals: usize,     // local slots in use
+    pub max_locals: usize, // local slots the frame needs (the slots of a left block are reused)
+
+    // symbols of the enclosing scopes captured by this scope, in the order they were captured
+    pub free_symbols: Vec<Symbol>,
+}
+
+/// Names defined in a block, they are not visible once the block is left
+#[derive(Debug, Clone)]
+struct Block {
+    store: HashMap<String, Symbol>,
+    first_local: usize, // slot of the first local of the block
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        return SymbolTable::default();
+    }
+
+    /// Creates the table of a scope nested in `outer` (ex: a function body)
+    pub fn new_enclosed(outer: SymbolTable) -> Self {
+        return SymbolTable {
+            outer: Some(Box::new(outer)),
+            ..Default::default()
+        };
+    }
+
+    /// Names defined from now on are locals of the block (even at the top level)
+    pub fn enter_block(&mut self) {
+        self.blocks.push(Block {
+            store: HashMap::new(),
+            first_local: self.num_locals,
+        });
+    }
+
+    pub fn leave_block(&mut self) {
+        let block = self
+            .blocks
+            .pop()
+            .expect("Only an entered block can be left");
+        self.num_locals = block.first_local;
+    }
+
+    pub fn define(&mut self, name: &str) -> Symbol {
+        let symbol = if self.outer.is_none() && self.blocks.is_empty() {
+            self.num_globals += 1;
+            Symbol {
+                name: name.to_string(),
+                scope: SymbolScope::Global,
+                index: self.num_globals - 1,
+            }
+        } else {
+            self.num_locals += 1;
+            self.max_locals = self.max_locals.max(self.num_locals);
+            Symbol {
+                name: name.to_string(),
+                scope: SymbolScope::Local,
+                index: self.num_locals - 1,
+            }
+        };
+
+        let store = match self.blocks.last_mut() {
+            Some(block) => &mut block.store,
+            None => &mut self.store,
+        };
+        store.insert(name.to_string(), symbol.clone());
+
+        return symbol;
+    }
+
+    /// Defines a builtin function, `index` being its position in the builtins list
+    pub fn define_builtin(&mut self, index: usize, name: &str) -> Symbol {
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope: SymbolScope::Builtin,
+            index,
+        };
+
+        self.store.insert(name.to_string(), symbol.clone());
+
+        return symbol;
+    }
+
+    /// Defines the name of the function owning this scope so that it can call itself
+    pub fn define_function_name(&mut self, name: &str) -> Symbol {
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope: SymbolScope::Function,
+            index: 0,
+        };
+
+        self.store.insert(name.to_string(), symbol.clone());
+
+        return symbol;
+    }
+
+    /// Looks the name up in this scope, then in the outer scopes
+    /// NOTE: locals of enclosing functions get captured as free symbols on the way
+    pub fn resolve(&mut self, name: &str) -> Option<Symbol> {
+        if let Some(symbol) = self.lookup(name) {
+            return Some(symbol.clone());
+        }
+
+        let symbol = self.outer.as_mut()?.resolve(name)?;
+        if matches!(symbol.scope, SymbolScope::Global | SymbolScope::Builtin) {
+            return Some(symbol);
+        }
+
+        return Some(self.define_free(symbol));
+    }
+
+    /// Variable already bound to the name in this scope (a `let` of the same name rebinds it
+    /// in place, as the evaluator does)
+    pub fn defined_here(&self, name: &str) -> Option<Symbol> {
+        let store = match self.blocks.last() {
+            Some(block) => &block.store,
+            None => &self.store,
+        };
+
+        return store
+            .get(name)
+            .filter(|symbol| matches!(symbol.scope, SymbolScope::Global | SymbolScope::Local))
+            .cloned();
+    }
+
+    /// Like `resolve`, but looks past the names of the functions being defined to find the
+    /// variable an assignment writes to
+    pub fn resolve_binding(&mut self, name: &str) -> Option<Symbol> {
+        let captured = match self.lookup(name) {
+            Some(symbol) if symbol.scope == SymbolScope::Free => Some(symbol.clone()),
+            Some(symbol) if symbol.scope != SymbolScope::Function => return Some(symbol.clone()),
+            _ => None,
+        };
+
+        let symbol = self.outer.as_mut()?.resolve_binding(name)?;
+        if matches!(symbol.scope, SymbolScope::Global | SymbolScope::Builtin) {
+            return Some(symbol);
+        }
+
+        if let Some(captured) = captured
+            && self.free_symbols[captured.index] == symbol
+        {
+            return Some(captured);
+        }
+
+        return Some(self.define_free(symbol));
+    }
+
+    /// Symbol of the name in this scope, the innermost block first
+    fn lookup(&self, name: &str) -> Option<&Symbol> {
+        return self
+            .blocks
+            .iter()
+            .rev()
+            .find_map(|block| block.store.get(name))
+            .or_else(|| self.store.get(name));
+    }
+
+    fn define_free(&mut self, original: Symbol) -> Symbol {
+        let symbol = Symbol {
+            name: original.name.clone(),
+            scope: SymbolScope::Free,
+            index: self.free_symbols.len(),
+        };
+
+        self.free_symbols.push(original);
+        self.store.insert(symbol.name.clone(), symbol.clone());
+
+        return symbol;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Symbol, SymbolScope, SymbolTable};
+
+    fn symbol(name: &str, scope: SymbolScope, index: usize) -> Symbol {
+        return Symbol {
+            name: name.to_string(),
+            scope,
+            index,
+        };
+    }
+
+    #[test]
+    fn test_define_and_resolve() {
+        let mut global = SymbolTable::new();
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 0));
+        assert_eq!(global.define("b"), symbol("b", SymbolScope::Global, 1));
+
+        let mut local = SymbolTable::new_enclosed(global);
+        assert_eq!(local.define("c"), symbol("c", SymbolScope::Local, 0));
+
+        assert_eq!(
+            local.resolve("a"),
+            Some(symbol("a", SymbolScope::Global, 0))
+        );
+        assert_eq!(local.resolve("c"), Some(symbol("c", SymbolScope::Local, 0)));
+        assert_eq!(local.resolve("d"), None);
+    }
+
+    #[test]
+    fn test_resolve_free() {
+        let mut global = SymbolTable::new();
+        global.define("a");
+
+        let mut first_local = SymbolTable::new_enclosed(global);
+        first_local.define("b");
+
+        let mut second_local = SymbolTable::new_enclosed(first_local);
+        second_local.define("c");
+
+        assert_eq!(
+            second_local.resolve("a"),
+            Some(symbol("a", SymbolScope::Global, 0))
+        );
+        assert_eq!(
+            second_local.resolve("b"),
+            Some(symbol("b", SymbolScope::Free, 0))
+        );
+        assert_eq!(
+            second_local.resolve("c"),
+            Some(symbol("c", SymbolScope::Local, 0))
+        );
+        assert_eq!(
+            second_local.free_symbols,
+            vec![symbol("b", SymbolScope::Local, 0)]
+        );
+    }
+
+    #[test]
+    fn test_define_resolve_builtins() {
+        let mut global = SymbolTable::new();
+        global.define_builtin(0, "a");
+        global.define_builtin(1, "b");
+
+        let mut first_local = SymbolTable::new_enclosed(global);
+        let mut second_local = SymbolTable::new_enclosed(first_local.clone());
+
+        for table in [&mut first_local, &mut second_local] {
+            assert_eq!(
+                table.resolve("a"),
+                Some(symbol("a", SymbolScope::Builtin, 0))
+            );
+            assert_eq!(
+                table.resolve("b"),
+                Some(symbol("b", SymbolScope::Builtin, 1))
+            );
+            assert!(table.free_symbols.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_shadowing_function_name() {
+        let mut local = SymbolTable::new_enclosed(SymbolTable::new());
+        local.define_function_name("a");
+        assert_eq!(
+            local.resolve("a"),
+            Some(symbol("a", SymbolScope::Function, 0))
+        );
+
+        local.define("a");
+        assert_eq!(local.resolve("a"), Some(symbol("a", SymbolScope::Local, 0)));
+    }
+
+    #[test]
+    fn test_blocks() {
+        let mut global = SymbolTable::new();
+        global.define("a");
+
+        global.enter_block();
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Local, 0));
+        assert_eq!(
+            global.resolve("a"),
+            Some(symbol("a", SymbolScope::Local, 0))
+        );
+
+        global.enter_block();
+        assert_eq!(global.define("b"), symbol("b", SymbolScope::Local, 1));
+        assert_eq!(global.defined_here("a"), None);
+        assert_eq!(
+            global.resolve("a"),
+            Some(symbol("a", SymbolScope::Local, 0))
+        );
+        global.leave_block();
+
+        assert_eq!(global.resolve("b"), None);
+        assert_eq!(global.define("c"), symbol("c", SymbolScope::Local, 1));
+        global.leave_block();
+
+        assert_eq!(
+            global.resolve("a"),
+            Some(symbol("a", SymbolScope::Global, 0))
+        );
+        assert_eq!(global.define("d"), symbol("d", SymbolScope::Global, 1));
+        assert_eq!(global.max_locals, 2);
+
+        let mut local = SymbolTable::new_enclosed(global);
+        local.enter_block();
+        local.define("e");
+        local.leave_block();
+
+        let mut closure = SymbolTable::new_enclosed(local.clone());
+        assert_eq!(closure.resolve("e"), None);
+
+        local.enter_block();
+        local.define("e");
+        closure = SymbolTable::new_enclosed(local);
+        assert_eq!(
+            closure.resolve("e"),
+            Some(symbol("e", SymbolScope::Free, 0))
+        );
+        assert_eq!(
+            closure.free_symbols,
+            vec![symbol("e", SymbolScope::Local, 0)]
+        );
+    }
+
+    #[test]
+    fn test_resolve_binding() {
+        let mut global = SymbolTable::new();
+        global.define("a");
+
+        let mut first_local = SymbolTable::new_enclosed(global);
+        first_local.define("b");
+        assert_eq!(
+            first_local.defined_here("b"),
+            Some(symbol("b", SymbolScope::Local, 0))
+        );
+        assert_eq!(first_local.defined_here("a"), None);
+
+        let mut second_local = SymbolTable::new_enclosed(first_local);
+        second_local.define_function_name("a");
+        second_local.define_function_name("b");
+
+        assert_eq!(
+            second_local.resolve_binding("a"),
+            Some(symbol("a", SymbolScope::Global, 0))
+        );
+        assert_eq!(
+            second_local.resolve_binding("b"),
+            Some(symbol("b", SymbolScope::Free, 0))
+        );
+        assert_eq!(
+            second_local.resolve_binding("b"),
+            Some(symbol("b", SymbolScope::Free, 0))
+        );
+        assert_eq!(
+            second_local.free_symbols,
+            vec![symbol("b", SymbolScope::Local, 0)]
+        );
+        assert_eq!(second_local.resolve_binding("c"), None);
+    }
+}
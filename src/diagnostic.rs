@@ -0,0 +1,228 @@
+use crate::token::Span;
+
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        };
+    }
+}
+
+/// Secondary location shown along with the primary span (ex: where a delimiter was opened)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+/// Problem found in the source code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+
+    pub span: Span, // underlined with carets
+    pub labels: Vec<Label>,
+
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: String, span: Span) -> Self {
+        return Diagnostic {
+            severity: Severity::Error,
+            code,
+            message,
+            span,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: None,
+        };
+    }
+
+    pub fn with_label(mut self, span: Span, message: &str) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.to_string(),
+        });
+        return self;
+    }
+
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(note.to_string());
+        return self;
+    }
+
+    pub fn with_help(mut self, help: &str) -> Self {
+        self.help = Some(help.to_string());
+        return self;
+    }
+
+    /// Multi-line report quoting the lines of `source` the diagnostic points at
+    /// `origin` names the source (ex: the path of the file)
+    ///
+    /// error[E0001]: Expected next token to be Assign, got Int(5) instead
+    ///  --> main.monkey:1:7
+    ///   |
+    /// 1 | let x 5
+    ///   |       ^
+    pub fn render(&self, source: &str, origin: &str) -> String {
+        // (span, underline character, label)
+        let mut annotations = vec![(self.span, '^', "")];
+        for label in &self.labels {
+            annotations.push((label.span, '-', label.message.as_str()));
+        }
+        annotations.sort_by_key(|(span, ..)| span.column);
+
+        let mut lines: Vec<usize> = annotations.iter().map(|(span, ..)| span.line).collect();
+        lines.sort();
+        lines.dedup();
+
+        let gutter = " ".repeat(lines.last().unwrap_or(&0).to_string().len());
+
+        let mut buf = format!("{}[{}]: {}\n", self.severity, self.code, self.message);
+        buf += &format!(
+            "{} --> {}:{}:{}\n",
+            &gutter[1..],
+            origin,
+            self.span.line,
+            self.span.column
+        );
+        buf += &format!("{} |", gutter);
+
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 && lines[i - 1] + 1 < *line {
+                buf += "\n...";
+            }
+
+            let (start, text) = source_line(source, *line);
+            let row = format!("{:>width$} | {}", line, text, width = gutter.len());
+            buf += &format!("\n{}", row.trim_end());
+
+            for (span, underline, label) in
+                annotations.iter().filter(|(span, ..)| span.line == *line)
+            {
+                // keep the tabs so that the underline is aligned with the source
+                let indent: String = text
+                    .chars()
+                    .take(span.column - 1)
+                    .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+                    .collect();
+
+                // the underline stops at the end of the line
+                let end = span.end.clamp(span.start, start + text.len());
+                let width = source[span.start.min(end)..end].chars().count().max(1);
+
+                let marker = format!(
+                    "{}{} {}",
+                    indent,
+                    underline.to_string().repeat(width),
+                    label
+                );
+                buf += &format!("\n{} | {}", gutter, marker.trim_end());
+            }
+        }
+
+        for note in &self.notes {
+            buf += &format!("\n{} = note: {}", gutter, note);
+        }
+
+        if let Some(help) = &self.help {
+            buf += &format!("\n{} = help: {}", gutter, help);
+        }
+
+        return buf;
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "{}: {}", self.span, self.message);
+    }
+}
+
+/// Byte offset and text of a line (1-based), empty past the end of the source
+fn source_line(source: &str, line: usize) -> (usize, &str) {
+    let mut start = 0;
+
+    for (i, text) in source.split('\n').enumerate() {
+        if i + 1 == line {
+            return (start, text.trim_end_matches('\r'));
+        }
+        start += text.len() + 1;
+    }
+
+    return (source.len(), "");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Diagnostic;
+    use crate::token::Span;
+
+    #[test]
+    fn test_render() {
+        let source = "let x = 1;\nlet add = fn(a, b) {\n\ta + * b\n};\n";
+        let span = |start, end, line, column| Span {
+            start,
+            end,
+            line,
+            column,
+        };
+
+        let diagnostic = Diagnostic::error(
+            "E0002",
+            "No prefix parse function for Asterisk found".to_string(),
+            span(37, 38, 3, 6),
+        );
+        assert_eq!(
+            diagnostic.render(source, "main.monkey"),
+            "error[E0002]: No prefix parse function for Asterisk found
+ --> main.monkey:3:6
+  |
+3 | \ta + * b
+  | \t    ^"
+        );
+
+        let diagnostic = Diagnostic::error(
+            "E0001",
+            "Expected next token to be RParen, got Eof instead".to_string(),
+            span(44, 44, 5, 1),
+        )
+        .with_label(span(0, 3, 1, 1), "some label")
+        .with_label(span(23, 24, 2, 13), "`(` opened here")
+        .with_note("a note")
+        .with_help("some help");
+        assert_eq!(
+            diagnostic.render(source, "<repl>"),
+            "error[E0001]: Expected next token to be RParen, got Eof instead
+ --> <repl>:5:1
+  |
+1 | let x = 1;
+  | --- some label
+2 | let add = fn(a, b) {
+  |             - `(` opened here
+...
+5 |
+  | ^
+  = note: a note
+  = help: some help"
+        );
+
+        assert_eq!(
+            diagnostic.to_string(),
+            "line 5, column 1: Expected next token to be RParen, got Eof instead"
+        );
+    }
+}
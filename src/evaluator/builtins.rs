@@ -0,0 +1,287 @@
+use crate::evaluator::Object;
+
+pub type BuiltinFunction = fn(&[Object]) -> Result<Object, String>;
+
+/// Function implemented in Rust that every program can call
+#[derive(Debug, Clone, Copy)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub function: BuiltinFunction,
+}
+
+// function pointers can not be reliably compared, the name is enough
+impl PartialEq for Builtin {
+    fn eq(&self, other: &Self) -> bool {
+        return self.name == other.name;
+    }
+}
+
+/// Every builtin function
+/// WARN: the compiled code refers to builtins by their index, only append to this list
+pub const BUILTINS: [Builtin; 7] = [
+    Builtin {
+        name: "len",
+        function: len,
+    },
+    Builtin {
+        name: "first",
+        function: first,
+    },
+    Builtin {
+        name: "last",
+        function: last,
+    },
+    Builtin {
+        name: "rest",
+        function: rest,
+    },
+    Builtin {
+        name: "push",
+        function: push,
+    },
+    Builtin {
+        name: "int",
+        function: int,
+    },
+    Builtin {
+        name: "float",
+        function: float,
+    },
+];
+
+pub fn lookup(name: &str) -> Option<Builtin> {
+    return BUILTINS
+        .iter()
+        .find(|builtin| builtin.name == name)
+        .copied();
+}
+
+// ======================================================
+// Builtin Functions
+
+fn len(arguments: &[Object]) -> Result<Object, String> {
+    check_argument_count(arguments, 1)?;
+
+    return match &arguments[0] {
+        Object::String(value) => Ok(Object::Integer(value.chars().count() as i64)),
+        Object::Array(elements) => Ok(Object::Integer(elements.len() as i64)),
+        other => Err(format!(
+            "argument to `len` not supported, got {}",
+            other.object_type()
+        )),
+    };
+}
+
+fn first(arguments: &[Object]) -> Result<Object, String> {
+    check_argument_count(arguments, 1)?;
+    let elements = array_argument("first", &arguments[0])?;
+
+    return Ok(elements.first().cloned().unwrap_or(Object::Null));
+}
+
+fn last(arguments: &[Object]) -> Result<Object, String> {
+    check_argument_count(arguments, 1)?;
+    let elements = array_argument("last", &arguments[0])?;
+
+    return Ok(elements.last().cloned().unwrap_or(Object::Null));
+}
+
+/// Every element but the first one, in a new array
+fn rest(arguments: &[Object]) -> Result<Object, String> {
+    check_argument_count(arguments, 1)?;
+    let elements = array_argument("rest", &arguments[0])?;
+
+    if elements.is_empty() {
+        return Ok(Object::Null);
+    }
+
+    return Ok(Object::Array(elements[1..].to_vec()));
+}
+
+/// Copy of the array with the element added at the end (the array itself is left untouched)
+fn push(arguments: &[Object]) -> Result<Object, String> {
+    check_argument_count(arguments, 2)?;
+    let elements = array_argument("push", &arguments[0])?;
+
+    let mut new_elements = Vec::with_capacity(elements.len() + 1);
+    new_elements.extend_from_slice(elements);
+    new_elements.push(arguments[1].clone());
+
+    return Ok(Object::Array(new_elements));
+}
+
+/// Integer value of a number (floats are truncated towards zero) or of a string
+fn int(arguments: &[Object]) -> Result<Object, String> {
+    check_argument_count(arguments, 1)?;
+
+    return match &arguments[0] {
+        Object::Integer(value) => Ok(Object::Integer(*value)),
+        // -2^63 fits but 2^63 does not
+        Object::Float(value) if (i64::MIN as f64..-(i64::MIN as f64)).contains(value) => {
+            Ok(Object::Integer(*value as i64))
+        }
+        Object::Float(value) => Err(format!("cannot convert {:?} to INTEGER", value)),
+        Object::String(value) => match value.trim().parse::<i64>() {
+            Ok(value) => Ok(Object::Integer(value)),
+            Err(_) => Err(format!("cannot convert \"{}\" to INTEGER", value)),
+        },
+        other => Err(format!(
+            "argument to `int` not supported, got {}",
+            other.object_type()
+        )),
+    };
+}
+
+/// Float value of a number or of a string
+fn float(arguments: &[Object]) -> Result<Object, String> {
+    check_argument_count(arguments, 1)?;
+
+    return match &arguments[0] {
+        Object::Integer(value) => Ok(Object::Float(*value as f64)),
+        Object::Float(value) => Ok(Object::Float(*value)),
+        Object::String(value) => match value.trim().parse::<f64>() {
+            Ok(value) => Ok(Object::Float(value)),
+            Err(_) => Err(format!("cannot convert \"{}\" to FLOAT", value)),
+        },
+        other => Err(format!(
+            "argument to `float` not supported, got {}",
+            other.object_type()
+        )),
+    };
+}
+
+// ======================================================
+// Helpers
+
+fn check_argument_count(arguments: &[Object], expected: usize) -> Result<(), String> {
+    if arguments.len() != expected {
+        return Err(format!(
+            "wrong number of arguments: expected {}, got {}",
+            expected,
+            arguments.len()
+        ));
+    }
+
+    return Ok(());
+}
+
+fn array_argument<'a>(name: &str, argument: &'a Object) -> Result<&'a [Object], String> {
+    return match argument {
+        Object::Array(elements) => Ok(elements),
+        other => Err(format!(
+            "argument to `{}` must be ARRAY, got {}",
+            name,
+            other.object_type()
+        )),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Object, lookup};
+
+    fn array(values: &[i64]) -> Object {
+        return Object::Array(values.iter().map(|v| Object::Integer(*v)).collect());
+    }
+
+    #[test]
+    fn test_builtins() {
+        let tests = [
+            (
+                "len",
+                vec![Object::String("héllo".to_string())],
+                Ok(Object::Integer(5)),
+            ),
+            ("len", vec![array(&[1, 2])], Ok(Object::Integer(2))),
+            (
+                "len",
+                vec![Object::Integer(1)],
+                Err("argument to `len` not supported, got INTEGER"),
+            ),
+            (
+                "len",
+                vec![],
+                Err("wrong number of arguments: expected 1, got 0"),
+            ),
+            ("first", vec![array(&[1, 2])], Ok(Object::Integer(1))),
+            ("first", vec![array(&[])], Ok(Object::Null)),
+            (
+                "first",
+                vec![Object::Integer(1)],
+                Err("argument to `first` must be ARRAY, got INTEGER"),
+            ),
+            ("last", vec![array(&[1, 2])], Ok(Object::Integer(2))),
+            ("last", vec![array(&[])], Ok(Object::Null)),
+            ("rest", vec![array(&[1, 2, 3])], Ok(array(&[2, 3]))),
+            ("rest", vec![array(&[1])], Ok(array(&[]))),
+            ("rest", vec![array(&[])], Ok(Object::Null)),
+            (
+                "push",
+                vec![array(&[1]), Object::Integer(2)],
+                Ok(array(&[1, 2])),
+            ),
+            (
+                "push",
+                vec![Object::Integer(1), Object::Integer(2)],
+                Err("argument to `push` must be ARRAY, got INTEGER"),
+            ),
+            ("int", vec![Object::Integer(3)], Ok(Object::Integer(3))),
+            ("int", vec![Object::Float(3.9)], Ok(Object::Integer(3))),
+            ("int", vec![Object::Float(-3.9)], Ok(Object::Integer(-3))),
+            (
+                "int",
+                vec![Object::String(" 42 ".to_string())],
+                Ok(Object::Integer(42)),
+            ),
+            (
+                "int",
+                vec![Object::Float(f64::NAN)],
+                Err("cannot convert NaN to INTEGER"),
+            ),
+            (
+                "int",
+                vec![Object::Float(1e19)],
+                Err("cannot convert 1e19 to INTEGER"),
+            ),
+            (
+                "int",
+                vec![Object::String("4.2".to_string())],
+                Err("cannot convert \"4.2\" to INTEGER"),
+            ),
+            (
+                "int",
+                vec![Object::Boolean(true)],
+                Err("argument to `int` not supported, got BOOLEAN"),
+            ),
+            ("float", vec![Object::Integer(2)], Ok(Object::Float(2.0))),
+            ("float", vec![Object::Float(0.5)], Ok(Object::Float(0.5))),
+            (
+                "float",
+                vec![Object::String("1e-3".to_string())],
+                Ok(Object::Float(0.001)),
+            ),
+            (
+                "float",
+                vec![Object::String("abc".to_string())],
+                Err("cannot convert \"abc\" to FLOAT"),
+            ),
+            (
+                "float",
+                vec![Object::Null],
+                Err("argument to `float` not supported, got NULL"),
+            ),
+        ];
+
+        for (name, arguments, expected) in tests {
+            let builtin = lookup(name).expect("Builtin should exist");
+
+            assert_eq!(
+                (builtin.function)(&arguments),
+                expected.map_err(|msg| msg.to_string()),
+                "Wrong result for {name}({arguments:?})"
+            );
+        }
+
+        assert!(lookup("puts").is_none());
+    }
+}
@@ -0,0 +1,143 @@
+use crate::evaluator::object::Object;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Shared handle on an environment (closures keep their defining environment alive)
+pub type Env = Rc<RefCell<Environment>>;
+
+/// Bindings of a scope, chained to the scope it was created in
+#[derive(Debug, Default)]
+pub struct Environment {
+    store: HashMap<String, Object>,
+    outer: Option<Env>,
+    pub depth: usize, // number of function calls the scope runs in
+}
+
+impl Environment {
+    /// Creates a global environment
+    pub fn new() -> Env {
+        return Rc::new(RefCell::new(Environment::default()));
+    }
+
+    /// Creates an environment nested in `outer` (ex: a loop body)
+    pub fn new_enclosed(outer: &Env) -> Env {
+        let depth = outer.borrow().depth;
+        return Environment::new_call(outer, depth);
+    }
+
+    /// Creates the environment of a function call, nested in the scope the function was
+    /// defined in, `depth` being the number of calls in progress
+    pub fn new_call(outer: &Env, depth: usize) -> Env {
+        return Rc::new(RefCell::new(Environment {
+            store: HashMap::new(),
+            outer: Some(Rc::clone(outer)),
+            depth,
+        }));
+    }
+
+    /// Looks the name up in this scope, then in the outer scopes
+    pub fn get(&self, name: &str) -> Option<Object> {
+        return match self.store.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.outer.as_ref()?.borrow().get(name),
+        };
+    }
+
+    /// Bindings of this scope only, sorted by name
+    pub fn bindings(&self) -> Vec<(String, Object)> {
+        let mut bindings: Vec<(String, Object)> = self
+            .store
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+
+        return bindings;
+    }
+
+    /// Binds the name in this scope (shadows the outer scopes)
+    pub fn set(&mut self, name: &str, value: Object) {
+        self.store.insert(name.to_string(), value);
+    }
+
+    /// Rebinds the name in the closest scope that binds it (closures share their variables
+    /// with the scope they were defined in)
+    pub fn assign(&mut self, name: &str, value: Object) -> Result<(), String> {
+        if self.store.contains_key(name) {
+            self.store.insert(name.to_string(), value);
+            return Ok(());
+        }
+
+        return match &self.outer {
+            Some(outer) => outer.borrow_mut().assign(name, value),
+            None => Err(format!("identifier not found: {}", name)),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Environment, Object};
+
+    #[test]
+    fn test_outer_scope_chaining() {
+        let global = Environment::new();
+        global.borrow_mut().set("a", Object::Integer(1));
+        global.borrow_mut().set("b", Object::Integer(2));
+
+        let inner = Environment::new_enclosed(&global);
+        inner.borrow_mut().set("b", Object::Boolean(true));
+
+        assert_eq!(inner.borrow().get("a"), Some(Object::Integer(1)));
+        assert_eq!(inner.borrow().get("b"), Some(Object::Boolean(true)));
+        assert_eq!(global.borrow().get("b"), Some(Object::Integer(2)));
+        assert_eq!(inner.borrow().get("c"), None);
+
+        assert_eq!(
+            inner.borrow().bindings(),
+            vec![("b".to_string(), Object::Boolean(true))]
+        );
+        assert_eq!(
+            global.borrow().bindings(),
+            vec![
+                ("a".to_string(), Object::Integer(1)),
+                ("b".to_string(), Object::Integer(2))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assign() {
+        let global = Environment::new();
+        global.borrow_mut().set("a", Object::Integer(1));
+
+        let function = Environment::new_enclosed(&global);
+        function.borrow_mut().set("b", Object::Integer(2));
+
+        let closure = Environment::new_enclosed(&function);
+        closure.borrow_mut().set("c", Object::Integer(3));
+
+        assert_eq!(
+            closure.borrow_mut().assign("a", Object::Integer(10)),
+            Ok(())
+        );
+        assert_eq!(
+            closure.borrow_mut().assign("c", Object::Integer(30)),
+            Ok(())
+        );
+        assert_eq!(
+            closure.borrow_mut().assign("b", Object::Integer(20)),
+            Ok(())
+        );
+        assert_eq!(
+            closure.borrow_mut().assign("d", Object::Integer(40)),
+            Err("identifier not found: d".to_string())
+        );
+
+        assert_eq!(global.borrow().get("a"), Some(Object::Integer(10)));
+        assert_eq!(function.borrow().get("b"), Some(Object::Integer(20)));
+        assert_eq!(closure.borrow().get("c"), Some(Object::Integer(30)));
+    }
+}
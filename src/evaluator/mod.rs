@@ -0,0 +1,1123 @@
+pub mod builtins;
+pub mod environment;
+pub mod object;
+
+pub use builtins::Builtin;
+pub use environment::{Env, Environment};
+pub use object::{Closure, CompiledFunction, Function, HashKey, HashPair, Object};
+
+use crate::ast::*;
+use crate::vm::MAX_FRAMES;
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+/// Evaluates a whole program, unwrapping the `return` value if any
+pub fn eval(program: &Program, env: &Env) -> Object {
+    return eval_program(program, env).0;
+}
+
+/// Like `eval`, also tells whether the program was stopped by a top level `return`
+pub fn eval_program(program: &Program, env: &Env) -> (Object, bool) {
+    let mut result = Object::Null;
+
+    for statement in &program.statements {
+        result = eval_statement(statement, env);
+
+        match result {
+            Object::ReturnValue(value) => return (*value, true),
+            Object::Error(_) => return (result, false),
+            _ => {}
+        }
+    }
+
+    return (result, false);
+}
+
+// ======================================================
+// Statements
+
+fn eval_statement(statement: &Statement, env: &Env) -> Object {
+    match statement {
+        Statement::Expression(statement) => return eval_expression(&statement.expression, env),
+        Statement::Let(statement) => {
+            let value = eval_expression(&statement.value, env);
+            if value.is_error() {
+                return value;
+            }
+
+            env.borrow_mut().set(&statement.identifier.value, value);
+            return Object::Null;
+        }
+        Statement::Return(statement) => {
+            let value = eval_expression(&statement.return_value, env);
+            if value.is_error() {
+                return value;
+            }
+
+            return Object::ReturnValue(Box::new(value));
+        }
+        Statement::Assign(statement) => return eval_assign_statement(statement, env),
+        Statement::While(statement) => return eval_while_statement(statement, env),
+        Statement::For(statement) => return eval_for_statement(statement, env),
+        Statement::Break(_) => return Object::Break,
+        Statement::Continue(_) => return Object::Continue,
+        Statement::Error(statement) => {
+            return Object::Error(format!("invalid statement at {}", statement.span));
+        }
+    }
+}
+
+fn eval_assign_statement(statement: &AssignStatement, env: &Env) -> Object {
+    let name = &statement.identifier.value;
+
+    let current = match env.borrow().get(name) {
+        Some(current) => current,
+        None if builtins::lookup(name).is_some() => {
+            return Object::Error(format!("cannot assign to builtin: {}", name));
+        }
+        None => return Object::Error(format!("identifier not found: {}", name)),
+    };
+
+    let mut value = eval_expression(&statement.value, env);
+    if value.is_error() {
+        return value;
+    }
+
+    if let Some(operator) = statement.operator() {
+        value = eval_infix_expression(operator, current, value);
+        if value.is_error() {
+            return value;
+        }
+    }
+
+    if let Err(message) = env.borrow_mut().assign(name, value) {
+        return Object::Error(message);
+    }
+
+    return Object::Null;
+}
+
+/// Each iteration runs the body in a scope of its own
+fn eval_while_statement(statement: &WhileStatement, env: &Env) -> Object {
+    loop {
+        let condition = eval_expression(&statement.condition, env);
+        if condition.is_error() {
+            return condition;
+        }
+
+        if !is_truthy(&condition) {
+            return Object::Null;
+        }
+
+        let scope = Environment::new_enclosed(env);
+        match eval_block_statement(&statement.body, &scope) {
+            Object::Break => return Object::Null,
+            result @ (Object::ReturnValue(_) | Object::Error(_)) => return result,
+            _ => {}
+        }
+    }
+}
+
+/// The array is evaluated once, each iteration binds the variable in a scope of its own
+fn eval_for_statement(statement: &ForStatement, env: &Env) -> Object {
+    let elements = match eval_expression(&statement.iterable, env) {
+        Object::Array(elements) => elements,
+        error @ Object::Error(_) => return error,
+        other => return Object::Error(format!("cannot iterate over {}", other.object_type())),
+    };
+
+    for element in elements {
+        let scope = Environment::new_enclosed(env);
+        scope.borrow_mut().set(&statement.variable.value, element);
+
+        match eval_block_statement(&statement.body, &scope) {
+            Object::Break => return Object::Null,
+            result @ (Object::ReturnValue(_) | Object::Error(_)) => return result,
+            _ => {}
+        }
+    }
+
+    return Object::Null;
+}
+
+/// Evaluates the statements of a block
+/// NOTE: the return value is not unwrapped so that it stops the evaluation of the outer blocks
+fn eval_block_statement(block: &BlockStatement, env: &Env) -> Object {
+    let mut result = Object::Null;
+
+    for statement in &block.statements {
+        result = eval_statement(statement, env);
+
+        if matches!(
+            result,
+            Object::ReturnValue(_) | Object::Error(_) | Object::Break | Object::Continue
+        ) {
+            return result;
+        }
+    }
+
+    return result;
+}
+
+// ======================================================
+// Expressions
+
+fn eval_expression(expression: &Expression, env: &Env) -> Object {
+    match expression {
+        Expression::Integer(integer) => return Object::Integer(integer.value),
+        Expression::Float(float) => return Object::Float(float.value),
+        Expression::String(string) => return Object::String(string.value.clone()),
+        Expression::Boolean(boolean) => return Object::Boolean(boolean.value),
+        Expression::Identifier(identifier) => return eval_identifier(identifier, env),
+        Expression::Prefix(prefix) => {
+            let right = eval_expression(&prefix.right, env);
+            if right.is_error() {
+                return right;
+            }
+
+            return eval_prefix_expression(&prefix.operator, right);
+        }
+        Expression::Infix(infix) if infix.operator == "&&" || infix.operator == "||" => {
+            return eval_logical_expression(infix, env);
+        }
+        Expression::Infix(infix) => {
+            let left = eval_expression(&infix.left, env);
+            if left.is_error() {
+                return left;
+            }
+
+            let right = eval_expression(&infix.right, env);
+            if right.is_error() {
+                return right;
+            }
+
+            return eval_infix_expression(&infix.operator, left, right);
+        }
+        Expression::If(if_expression) => return eval_if_expression(if_expression, env),
+        Expression::Function(function) => {
+            return Object::Function(Rc::new(Function {
+                parameters: function.parameters.clone(),
+                body: function.body.clone(),
+                env: env.clone(),
+            }));
+        }
+        Expression::Call(call) => {
+            let function = eval_expression(&call.function, env);
+            if function.is_error() {
+                return function;
+            }
+
+            let arguments = match eval_expressions(&call.arguments, env) {
+                Ok(arguments) => arguments,
+                Err(error) => return error,
+            };
+
+            let depth = env.borrow().depth + 1;
+            return apply_function(function, arguments, depth);
+        }
+        Expression::Array(array) => {
+            return match eval_expressions(&array.elements, env) {
+                Ok(elements) => Object::Array(elements),
+                Err(error) => error,
+            };
+        }
+        Expression::Index(index) => {
+            let left = eval_expression(&index.left, env);
+            if left.is_error() {
+                return left;
+            }
+
+            let index = eval_expression(&index.index, env);
+            if index.is_error() {
+                return index;
+            }
+
+            return eval_index_expression(left, index);
+        }
+        Expression::Hash(hash) => return eval_hash_literal(hash, env),
+    }
+}
+
+/// Evaluates the expressions from left to right, stops at the first error
+fn eval_expressions(expressions: &[Expression], env: &Env) -> Result<Vec<Object>, Object> {
+    let mut values = Vec::with_capacity(expressions.len());
+
+    for expression in expressions {
+        let value = eval_expression(expression, env);
+        if value.is_error() {
+            return Err(value);
+        }
+        values.push(value);
+    }
+
+    return Ok(values);
+}
+
+/// Variables shadow the builtin functions
+fn eval_identifier(identifier: &Identifier, env: &Env) -> Object {
+    if let Some(value) = env.borrow().get(&identifier.value) {
+        return value;
+    }
+
+    return match builtins::lookup(&identifier.value) {
+        Some(builtin) => Object::Builtin(builtin),
+        None => Object::Error(format!("identifier not found: {}", identifier.value)),
+    };
+}
+
+/// `&&` and `||` only evaluate their right operand when the left one does not decide the result
+fn eval_logical_expression(infix: &InfixExpression, env: &Env) -> Object {
+    let left = eval_expression(&infix.left, env);
+    if left.is_error() {
+        return left;
+    }
+
+    match (infix.operator.as_str(), is_truthy(&left)) {
+        ("&&", false) => return Object::Boolean(false),
+        ("||", true) => return Object::Boolean(true),
+        _ => {}
+    }
+
+    let right = eval_expression(&infix.right, env);
+    if right.is_error() {
+        return right;
+    }
+
+    return Object::Boolean(is_truthy(&right));
+}
+
+fn eval_prefix_expression(operator: &str, right: Object) -> Object {
+    return match (operator, &right) {
+        ("!", _) => Object::Boolean(!is_truthy(&right)),
+        ("-", Object::Integer(value)) => match value.checked_neg() {
+            Some(negated) => Object::Integer(negated),
+            None => Object::Error(format!("integer overflow: -{}", value)),
+        },
+        ("-", Object::Float(value)) => Object::Float(-value),
+        _ => Object::Error(format!(
+            "unknown operator: {}{}",
+            operator,
+            right.object_type()
+        )),
+    };
+}
+
+fn eval_infix_expression(operator: &str, left: Object, right: Object) -> Object {
+    if let Some((left, right)) = Object::float_operands(&left, &right) {
+        return eval_float_infix_expression(operator, left, right);
+    }
+
+    return match (&left, &right) {
+        (Object::Integer(left), Object::Integer(right)) => {
+            eval_integer_infix_expression(operator, *left, *right)
+        }
+        (Object::String(left), Object::String(right)) => {
+            eval_string_infix_expression(operator, left, right)
+        }
+        _ if left.object_type() != right.object_type() => Object::Error(format!(
+            "type mismatch: {} {} {}",
+            left.object_type(),
+            operator,
+            right.object_type()
+        )),
+        (Object::Boolean(left), Object::Boolean(right)) if operator == "==" => {
+            Object::Boolean(left == right)
+        }
+        (Object::Boolean(left), Object::Boolean(right)) if operator == "!=" => {
+            Object::Boolean(left != right)
+        }
+        _ => Object::Error(format!(
+            "unknown operator: {} {} {}",
+            left.object_type(),
+            operator,
+            right.object_type()
+        )),
+    };
+}
+
+fn eval_integer_infix_expression(operator: &str, left: i64, right: i64) -> Object {
+    let result = match operator {
+        "+" => left.checked_add(right),
+        "-" => left.checked_sub(right),
+        "*" => left.checked_mul(right),
+        "/" => {
+            if right == 0 {
+                return Object::Error("division by zero".to_string());
+            }
+            left.checked_div(right)
+        }
+        "%" => {
+            if right == 0 {
+                return Object::Error("division by zero".to_string());
+            }
+            left.checked_rem(right)
+        }
+        "<" => return Object::Boolean(left < right),
+        ">" => return Object::Boolean(left > right),
+        "<=" => return Object::Boolean(left <= right),
+        ">=" => return Object::Boolean(left >= right),
+        "==" => return Object::Boolean(left == right),
+        "!=" => return Object::Boolean(left != right),
+        _ => {
+            return Object::Error(format!("unknown operator: INTEGER {} INTEGER", operator));
+        }
+    };
+
+    return match result {
+        Some(value) => Object::Integer(value),
+        None => Object::Error(format!("integer overflow: {} {} {}", left, operator, right)),
+    };
+}
+
+/// IEEE 754 semantics: dividing by zero gives an infinity (or NaN) and NaN is never equal to
+/// anything, itself included
+fn eval_float_infix_expression(operator: &str, left: f64, right: f64) -> Object {
+    return match operator {
+        "+" => Object::Float(left + right),
+        "-" => Object::Float(left - right),
+        "*" => Object::Float(left * right),
+        "/" => Object::Float(left / right),
+        "%" => Object::Float(left % right),
+        "<" => Object::Boolean(left < right),
+        ">" => Object::Boolean(left > right),
+        "<=" => Object::Boolean(left <= right),
+        ">=" => Object::Boolean(left >= right),
+        "==" => Object::Boolean(left == right),
+        "!=" => Object::Boolean(left != right),
+        _ => Object::Error(format!("unknown operator: FLOAT {} FLOAT", operator)),
+    };
+}
+
+fn eval_string_infix_expression(operator: &str, left: &str, right: &str) -> Object {
+    return match operator {
+        "+" => Object::String(format!("{}{}", left, right)),
+        "==" => Object::Boolean(left == right),
+        "!=" => Object::Boolean(left != right),
+        _ => Object::Error(format!("unknown operator: STRING {} STRING", operator)),
+    };
+}
+
+fn eval_if_expression(if_expression: &IfExpression, env: &Env) -> Object {
+    let condition = eval_expression(&if_expression.condition, env);
+    if condition.is_error() {
+        return condition;
+    }
+
+    if is_truthy(&condition) {
+        return eval_block_statement(&if_expression.consequence, env);
+    } else if let Some(alternative) = &if_expression.alternative {
+        return eval_block_statement(alternative, env);
+    } else {
+        return Object::Null;
+    }
+}
+
+fn eval_hash_literal(hash: &HashLiteral, env: &Env) -> Object {
+    let mut pairs = BTreeMap::new();
+
+    for (key_expression, value_expression) in &hash.pairs {
+        let key = eval_expression(key_expression, env);
+        if key.is_error() {
+            return key;
+        }
+
+        let hash_key = match key.hash_key() {
+            Ok(hash_key) => hash_key,
+            Err(message) => return Object::Error(message),
+        };
+
+        let value = eval_expression(value_expression, env);
+        if value.is_error() {
+            return value;
+        }
+
+        pairs.insert(hash_key, HashPair { key, value });
+    }
+
+    return Object::Hash(pairs);
+}
+
+/// Out of bounds indexes and missing keys give null
+fn eval_index_expression(left: Object, index: Object) -> Object {
+    return match (&left, &index) {
+        (Object::Hash(pairs), _) => match index.hash_key() {
+            Ok(hash_key) => pairs
+                .get(&hash_key)
+                .map(|pair| pair.value.clone())
+                .unwrap_or(Object::Null),
+            Err(message) => Object::Error(message),
+        },
+        (Object::Array(elements), Object::Integer(index)) => usize::try_from(*index)
+            .ok()
+            .and_then(|index| elements.get(index))
+            .cloned()
+            .unwrap_or(Object::Null),
+        _ => Object::Error(format!(
+            "index operator not supported: {}[{}]",
+            left.object_type(),
+            index.object_type()
+        )),
+    };
+}
+
+/// `depth` is the number of calls in progress once this one starts
+fn apply_function(function: Object, arguments: Vec<Object>, depth: usize) -> Object {
+    let function = match function {
+        Object::Function(function) => function,
+        Object::Builtin(builtin) => {
+            return match (builtin.function)(&arguments) {
+                Ok(result) => result,
+                Err(message) => Object::Error(message),
+            };
+        }
+        other => return Object::Error(format!("not a function: {}", other.object_type())),
+    };
+
+    if function.parameters.len() != arguments.len() {
+        return Object::Error(format!(
+            "wrong number of arguments: expected {}, got {}",
+            function.parameters.len(),
+            arguments.len()
+        ));
+    }
+
+    // the main program counts as a frame of the vm
+    if depth >= MAX_FRAMES {
+        return Object::Error("stack overflow".to_string());
+    }
+
+    // the body runs in a new scope nested in the scope the function was defined in
+    let call_env = Environment::new_call(&function.env, depth);
+    for (parameter, argument) in function.parameters.iter().zip(arguments) {
+        call_env.borrow_mut().set(&parameter.value, argument);
+    }
+
+    return match eval_block_statement(&function.body, &call_env) {
+        Object::ReturnValue(value) => *value,
+        result => result,
+    };
+}
+
+fn is_truthy(object: &Object) -> bool {
+    return match object {
+        Object::Null => false,
+        Object::Boolean(value) => *value,
+        _ => true,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Environment, HashKey, Object, eval};
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_eval_integer_expression() {
+        let tests = [
+            ("5", 5),
+            ("10", 10),
+            ("-5", -5),
+            ("--10", 10),
+            ("5 + 5 + 5 + 5 - 10", 10),
+            ("2 * 2 * 2 * 2 * 2", 32),
+            ("-50 + 100 + -50", 0),
+            ("5 * 2 + 10", 20),
+            ("5 + 2 * 10", 25),
+            ("20 + 2 * -10", 0),
+            ("50 / 2 * 2 + 10", 60),
+            ("2 * (5 + 10)", 30),
+            ("3 * 3 * 3 + 10", 37),
+            ("3 * (3 * 3) + 10", 37),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", 50),
+            ("7 % 3", 1),
+            ("-7 % 3", -1),
+            ("2 + 10 % 4 * 3", 8),
+            ("0xff + 0o10 + 0b11 - 1_000", -734),
+            ("-9223372036854775808 + 1", -9223372036854775807),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                run(input),
+                Object::Integer(expected),
+                "Wrong result for `{input}`"
+            );
+        }
+    }
+
+    #[test]
+    fn test_eval_boolean_expression() {
+        let tests = [
+            ("true", true),
+            ("false", false),
+            ("1 < 2", true),
+            ("1 > 2", false),
+            ("1 == 1", true),
+            ("1 != 1", false),
+            ("true == true", true),
+            ("true != false", true),
+            ("(1 < 2) == true", true),
+            ("(1 > 2) == true", false),
+            ("!true", false),
+            ("!!true", true),
+            ("!5", false),
+            ("!!5", true),
+            ("1 <= 2", true),
+            ("2 <= 2", true),
+            ("3 <= 2", false),
+            ("1 >= 2", false),
+            ("2 >= 2", true),
+            ("true && true", true),
+            ("true && false", false),
+            ("false || true", true),
+            ("false || false", false),
+            ("1 && \"\"", true),
+            ("1 < 2 && 2 < 3", true),
+            ("false || 1 > 2", false),
+            // the right side is not evaluated when the left one decides
+            ("false && undefined", false),
+            ("true || undefined", true),
+            ("false && 1 / 0", false),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                run(input),
+                Object::Boolean(expected),
+                "Wrong result for `{input}`"
+            );
+        }
+    }
+
+    #[test]
+    fn test_float_expressions() {
+        let tests = [
+            ("2.5", Object::Float(2.5)),
+            ("-0.5", Object::Float(-0.5)),
+            ("1.5 + 2.25", Object::Float(3.75)),
+            ("1 + 0.5", Object::Float(1.5)),
+            ("0.5 * 4", Object::Float(2.0)),
+            ("7 / 2.0", Object::Float(3.5)),
+            ("7.5 % 2", Object::Float(1.5)),
+            ("1 / 0.0", Object::Float(f64::INFINITY)),
+            ("-1 / 0.0", Object::Float(f64::NEG_INFINITY)),
+            ("1.5 < 2", Object::Boolean(true)),
+            ("2 >= 2.0", Object::Boolean(true)),
+            ("1 == 1.0", Object::Boolean(true)),
+            ("0.1 + 0.2 == 0.3", Object::Boolean(false)),
+            // NaN is unordered and never equal, even to itself
+            ("let nan = 0.0 / 0.0; nan == nan", Object::Boolean(false)),
+            ("let nan = 0.0 / 0.0; nan != nan", Object::Boolean(true)),
+            (
+                "let nan = 0.0 / 0.0; nan < 1 || nan >= 1",
+                Object::Boolean(false),
+            ),
+            ("int(7.9) + int(\"-2\")", Object::Integer(5)),
+            ("float(1) / 4", Object::Float(0.25)),
+            ("float(\"NaN\") == float(\"NaN\")", Object::Boolean(false)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "Wrong result for `{input}`");
+        }
+
+        assert_eq!(run("1.0").to_string(), "1.0");
+        assert_eq!(run("1e21 * 10").to_string(), "1e22");
+    }
+
+    #[test]
+    fn test_string_expressions() {
+        let tests = [
+            (
+                r#""Hello World!""#,
+                Object::String("Hello World!".to_string()),
+            ),
+            (
+                r#""Hello" + " " + "World!""#,
+                Object::String("Hello World!".to_string()),
+            ),
+            (
+                r#"let greet = fn(name) { "Hi " + name }; greet("🙈")"#,
+                Object::String("Hi 🙈".to_string()),
+            ),
+            (r#""a" == "a""#, Object::Boolean(true)),
+            (r#""a" != "a""#, Object::Boolean(false)),
+            (r#""a" == "b""#, Object::Boolean(false)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "Wrong result for `{input}`");
+        }
+    }
+
+    #[test]
+    fn test_array_expressions() {
+        let tests = [
+            (
+                "[1, 2 * 2, 3 + 3]",
+                Object::Array(vec![
+                    Object::Integer(1),
+                    Object::Integer(4),
+                    Object::Integer(6),
+                ]),
+            ),
+            ("[1, 2, 3][0]", Object::Integer(1)),
+            ("[1, 2, 3][1 + 1]", Object::Integer(3)),
+            ("let i = 0; [1][i];", Object::Integer(1)),
+            ("let a = [1, 2, 3]; a[0] + a[1] + a[2];", Object::Integer(6)),
+            ("[1, 2, 3][3]", Object::Null),
+            ("[1, 2, 3][-1]", Object::Null),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "Wrong result for `{input}`");
+        }
+    }
+
+    #[test]
+    fn test_hash_literals() {
+        let input = r#"let two = "two";
+{
+    "one": 10 - 9,
+    two: 1 + 1,
+    "thr" + "ee": 6 / 2,
+    4: 4,
+    true: 5,
+    false: 6
+}"#;
+
+        let pairs = match run(input) {
+            Object::Hash(pairs) => pairs,
+            other => panic!("Expected a Hash, got {}", other),
+        };
+
+        let expected = [
+            (HashKey::String("one".to_string()), 1),
+            (HashKey::String("two".to_string()), 2),
+            (HashKey::String("three".to_string()), 3),
+            (HashKey::Integer(4), 4),
+            (HashKey::Boolean(true), 5),
+            (HashKey::Boolean(false), 6),
+        ];
+
+        assert_eq!(pairs.len(), expected.len());
+        for (key, value) in expected {
+            assert_eq!(
+                pairs[&key].value,
+                Object::Integer(value),
+                "Wrong value for {key:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_hash_index_expressions() {
+        let tests = [
+            (r#"{"foo": 5}["foo"]"#, Object::Integer(5)),
+            (r#"{"foo": 5}["bar"]"#, Object::Null),
+            (r#"let key = "foo"; {"foo": 5}[key]"#, Object::Integer(5)),
+            (r#"{}["foo"]"#, Object::Null),
+            ("{5: 5}[5]", Object::Integer(5)),
+            ("{true: 5}[true]", Object::Integer(5)),
+            ("{false: 5}[false]", Object::Integer(5)),
+            (r#"{"a": 1, "a": 2}["a"]"#, Object::Integer(2)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "Wrong result for `{input}`");
+        }
+    }
+
+    #[test]
+    fn test_builtin_functions() {
+        let tests = [
+            (r#"len("")"#, Object::Integer(0)),
+            (r#"len("four")"#, Object::Integer(4)),
+            ("len([1, 2, 3])", Object::Integer(3)),
+            (
+                "len(1)",
+                Object::Error("argument to `len` not supported, got INTEGER".to_string()),
+            ),
+            ("first([1, 2, 3])", Object::Integer(1)),
+            ("last([1, 2, 3])", Object::Integer(3)),
+            (
+                "rest([1, 2, 3])",
+                Object::Array(vec![Object::Integer(2), Object::Integer(3)]),
+            ),
+            (
+                "let a = [1]; push(a, 2); a",
+                Object::Array(vec![Object::Integer(1)]),
+            ),
+            ("let len = fn(x) { 42 }; len([])", Object::Integer(42)),
+            (
+                "let map = fn(arr, f) { let iter = fn(arr, acc) { if (len(arr) == 0) { acc } else { iter(rest(arr), push(acc, f(first(arr)))) } }; iter(arr, []) }; map([1, 2, 3], fn(x) { x * 2 })",
+                Object::Array(vec![
+                    Object::Integer(2),
+                    Object::Integer(4),
+                    Object::Integer(6),
+                ]),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "Wrong result for `{input}`");
+        }
+    }
+
+    #[test]
+    fn test_if_else_expressions() {
+        let tests = [
+            ("if (true) { 10 }", Object::Integer(10)),
+            ("if (false) { 10 }", Object::Null),
+            ("if (1) { 10 }", Object::Integer(10)),
+            ("if (1 < 2) { 10 }", Object::Integer(10)),
+            ("if (1 > 2) { 10 } else { 20 }", Object::Integer(20)),
+            ("if (1 < 2) { 10 } else { 20 }", Object::Integer(10)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "Wrong result for `{input}`");
+        }
+    }
+
+    #[test]
+    fn test_return_statements() {
+        let tests = [
+            ("return 10;", 10),
+            ("return 10; 9;", 10),
+            ("return 2 * 5; 9;", 10),
+            ("9; return 2 * 5; 9;", 10),
+            ("if (10 > 1) { if (10 > 1) { return 10; } return 1; }", 10),
+            (
+                "let f = fn(x) { if (x > 1) { return x; } return 0; }; f(5) + 1;",
+                6,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                run(input),
+                Object::Integer(expected),
+                "Wrong result for `{input}`"
+            );
+        }
+    }
+
+    #[test]
+    fn test_error_handling() {
+        let tests = [
+            ("5 + true;", "type mismatch: INTEGER + BOOLEAN"),
+            ("5 + true; 5;", "type mismatch: INTEGER + BOOLEAN"),
+            ("-true", "unknown operator: -BOOLEAN"),
+            ("true + false;", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("5; true + false; 5", "unknown operator: BOOLEAN + BOOLEAN"),
+            (
+                "if (10 > 1) { if (10 > 1) { return true + false; } return 1; }",
+                "unknown operator: BOOLEAN + BOOLEAN",
+            ),
+            ("foobar", "identifier not found: foobar"),
+            ("10 / (5 - 5)", "division by zero"),
+            (
+                "9223372036854775807 + 1",
+                "integer overflow: 9223372036854775807 + 1",
+            ),
+            ("let x = 5; x(1)", "not a function: INTEGER"),
+            (r#""Hello" - "World""#, "unknown operator: STRING - STRING"),
+            (r#""Hello" + 1"#, "type mismatch: STRING + INTEGER"),
+            ("1[0]", "index operator not supported: INTEGER[INTEGER]"),
+            (
+                r#"{"name": "Monkey"}[fn(x) { x }];"#,
+                "unusable as hash key: FUNCTION",
+            ),
+            ("{[1]: 2}", "unusable as hash key: ARRAY"),
+            (
+                "fn(a, b) { a }(1)",
+                "wrong number of arguments: expected 2, got 1",
+            ),
+            ("10 % 0", "division by zero"),
+            ("x = 1", "identifier not found: x"),
+            ("for (x in 5) { }", "cannot iterate over INTEGER"),
+            ("for (x in [1]) { } x", "identifier not found: x"),
+            ("for (x in [1]) { let y = x; } y", "identifier not found: y"),
+            (
+                "while (true) { let y = 1; break; } y",
+                "identifier not found: y",
+            ),
+            ("for (x in {}) { }", "cannot iterate over HASH"),
+            ("while (1 + true) { }", "type mismatch: INTEGER + BOOLEAN"),
+            ("1.5 + true", "type mismatch: FLOAT + BOOLEAN"),
+            ("-\"a\" * 1.5", "unknown operator: -STRING"),
+            ("{1.5: 1}", "unusable as hash key: FLOAT"),
+            ("int(0.0 / 0.0)", "cannot convert NaN to INTEGER"),
+            ("1 <= true", "type mismatch: INTEGER <= BOOLEAN"),
+            ("true && undefined", "identifier not found: undefined"),
+            ("x += 1", "identifier not found: x"),
+            ("len += 1", "cannot assign to builtin: len"),
+            ("let x = 1; x += true", "type mismatch: INTEGER + BOOLEAN"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                run(input),
+                Object::Error(expected.to_string()),
+                "Wrong result for `{input}`"
+            );
+        }
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let tests = [
+            ("let f = fn(x) { f(x) }; f(1)", "ERROR: stack overflow"),
+            (
+                "let f = fn(n) { if (n == 0) { 0 } else { f(n - 1) } }; f(3000)",
+                "ERROR: stack overflow",
+            ),
+            // the same limit as the vm: the main program and 1023 calls
+            (
+                "let f = fn(n) { if (n == 0) { 0 } else { f(n - 1) } }; f(1022)",
+                "0",
+            ),
+        ];
+
+        // the evaluator recurses natively, the test threads have a smaller stack than the main one
+        let handle = std::thread::Builder::new()
+            .stack_size(8 * 1024 * 1024)
+            .spawn(move || {
+                for (input, expected) in tests {
+                    assert_eq!(
+                        run(input).to_string(),
+                        expected,
+                        "Wrong result for `{input}`"
+                    );
+                }
+            })
+            .unwrap();
+
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_let_statements() {
+        let tests = [
+            ("let a = 5; a;", 5),
+            ("let a = 5 * 5; a;", 25),
+            ("let a = 5; let b = a; b;", 5),
+            ("let a = 5; let b = a; let c = a + b + 5; c;", 15),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                run(input),
+                Object::Integer(expected),
+                "Wrong result for `{input}`"
+            );
+        }
+    }
+
+    #[test]
+    fn test_assign_statements() {
+        let tests = [
+            ("let a = 5; a += 2; a;", 7),
+            ("let a = 5; a -= 2; a;", 3),
+            ("let a = 5; a *= 2; a *= 3; a;", 30),
+            ("let a = 9; a /= 2; a;", 4),
+            // locals of the current call
+            ("let f = fn(x) { x += 10; let y = x; y *= 2; y }; f(1);", 22),
+            // globals from a function
+            (
+                "let total = 0; let add = fn(n) { total += n; }; add(2); add(3); total;",
+                5,
+            ),
+            ("let f = fn() { f = 1; }; f(); f", 1),
+            // captured variables are shared with the scope they come from
+            (
+                "let counter = fn() { let c = 0; fn() { c += 1; c } }; let next = counter(); next(); next(); next()",
+                3,
+            ),
+            (
+                "let f = fn() { let x = 1; let g = fn() { x }; x = 5; g() }; f()",
+                5,
+            ),
+            (
+                "let f = fn() { let x = 0; let g = fn() { fn() { x += 1; } }; let h = g(); h(); h(); x }; f()",
+                2,
+            ),
+            ("let x = 1; let f = fn() { x }; let x = 2; f()", 2),
+            (
+                "let f = fn() { let x = 1; let g = fn() { x }; let x = 2; g() }; f()",
+                2,
+            ),
+            (
+                "let f = fn() { let g = fn() { g = 5; 1 }; g() + g }; f()",
+                6,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                run(input),
+                Object::Integer(expected),
+                "Wrong result for `{input}`"
+            );
+        }
+
+        assert_eq!(run("let a = 1; a += 1"), Object::Null);
+    }
+
+    #[test]
+    fn test_loops() {
+        let tests = [
+            ("let x = 1; x = x + 1; x", Object::Integer(2)),
+            (
+                "let i = 0; while (i < 5) { i = i + 1; } i",
+                Object::Integer(5),
+            ),
+            (
+                "let i = 0; while (true) { i += 1; if (i == 3) { break; } } i",
+                Object::Integer(3),
+            ),
+            (
+                "let i = 0; let odd = 0; while (i < 10) { i += 1; if (i % 2 == 0) { continue; } odd += 1; } odd",
+                Object::Integer(5),
+            ),
+            (
+                "let sum = 0; for (x in [1, 2, 3]) { sum += x; } sum",
+                Object::Integer(6),
+            ),
+            ("let n = 0; for (x in []) { n += 1; } n", Object::Integer(0)),
+            // `if`s used as statements can leave the loop from nested blocks
+            (
+                "let i = 0; while (true) { i += 1; if (i > 2) { if (true) { break } } } i",
+                Object::Integer(3),
+            ),
+            (
+                "let n = 0; let i = 0; while (i < 5000) { i += 1; if (i % 2 == 0) { continue } else { n += 1 } } n",
+                Object::Integer(2500),
+            ),
+            // each iteration has its own scope
+            ("let x = 10; for (x in [1, 2]) { } x", Object::Integer(10)),
+            (
+                "let x = 10; let i = 0; while (i < 2) { let x = i; i += 1; } x",
+                Object::Integer(10),
+            ),
+            (
+                "let n = 0; for (x in [1, 2]) { for (x in [10, 20]) { n += x; } n += x; } n",
+                Object::Integer(63),
+            ),
+            (
+                "let fs = []; for (i in [1, 2, 3]) { fs = push(fs, fn() { i }); } [fs[0](), fs[2]()]",
+                Object::Array(vec![Object::Integer(1), Object::Integer(3)]),
+            ),
+            (
+                "let fs = []; let i = 0; while (i < 3) { let j = i; fs = push(fs, fn() { j }); i += 1; } [fs[0](), fs[2]()]",
+                Object::Array(vec![Object::Integer(0), Object::Integer(2)]),
+            ),
+            (
+                "let fs = []; for (i in [1, 2]) { fs = push(fs, fn() { i += 10; i }); } fs[0](); [fs[0](), fs[1]()]",
+                Object::Array(vec![Object::Integer(21), Object::Integer(12)]),
+            ),
+            (
+                "let pairs = 0; for (a in [1, 2, 3]) { for (b in [1, 2, 3]) { if (b > a) { break; } pairs += 1; } } pairs",
+                Object::Integer(6),
+            ),
+            (
+                "let s = \"\"; for (w in [\"a\", \"b\", \"c\"]) { if (w == \"b\") { continue; } s += w; } s",
+                Object::String("ac".to_string()),
+            ),
+            // the array is evaluated once
+            (
+                "let xs = [1, 2]; let n = 0; for (x in xs) { xs = push(xs, x); n += 1; } n",
+                Object::Integer(2),
+            ),
+            (
+                "let find = fn(xs, y) { let i = 0; for (x in xs) { if (x == y) { return i; } i += 1; } -1 }; [find([5, 6, 7], 7), find([], 1)]",
+                Object::Array(vec![Object::Integer(2), Object::Integer(-1)]),
+            ),
+            (
+                "let count = fn(n) { let i = 0; while (i < n) { i += 1; } i }; count(100000)",
+                Object::Integer(100000),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "Wrong result for `{input}`");
+        }
+    }
+
+    #[test]
+    fn test_function_application() {
+        let tests = [
+            ("let identity = fn(x) { x; }; identity(5);", 5),
+            ("let identity = fn(x) { return x; }; identity(5);", 5),
+            ("let double = fn(x) { x * 2; }; double(5);", 10),
+            ("let add = fn(x, y) { x + y; }; add(5, 5);", 10),
+            ("let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));", 20),
+            ("fn(x) { x; }(5)", 5),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                run(input),
+                Object::Integer(expected),
+                "Wrong result for `{input}`"
+            );
+        }
+    }
+
+    #[test]
+    fn test_closures() {
+        let input = "
+let new_adder = fn(x) {
+    fn(y) { x + y };
+};
+
+let add_two = new_adder(2);
+add_two(2);";
+
+        assert_eq!(run(input), Object::Integer(4));
+    }
+
+    #[test]
+    fn test_recursion() {
+        let input = "
+let fib = fn(n) {
+    if (n < 2) { return n; }
+    fib(n - 1) + fib(n - 2)
+};
+fib(15);";
+
+        assert_eq!(run(input), Object::Integer(610));
+    }
+
+    #[test]
+    fn test_environment_persists() {
+        let env = Environment::new();
+
+        for (input, expected) in [("let a = 2;", Object::Null), ("a * 3", Object::Integer(6))] {
+            let mut lexer = Lexer::new(input);
+            let mut parser = Parser::new(&mut lexer);
+            let program = parser.parse_program();
+
+            assert_eq!(eval(&program, &env), expected);
+        }
+    }
+
+    // ===========================================
+    // HELPERS
+    fn run(input: &str) -> Object {
+        let mut lexer = Lexer::new(input);
+        let mut parser = Parser::new(&mut lexer);
+
+        let program = parser.parse_program();
+        assert!(
+            parser.get_errors().is_empty(),
+            "Parser had errors: {:?}",
+            parser.get_errors()
+        );
+
+        return eval(&program, &Environment::new());
+    }
+}
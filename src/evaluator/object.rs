@@ -0,0 +1,221 @@
+use crate::ast::{BlockStatement, Identifier, Node};
+use crate::code::Instructions;
+use crate::evaluator::builtins::Builtin;
+use crate::evaluator::environment::Env;
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::rc::Rc;
+
+/// Values produced by the evaluator
+#[derive(Debug, Clone, PartialEq)]
+pub enum Object {
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Boolean(bool),
+    Array(Vec<Object>),
+    Hash(BTreeMap<HashKey, HashPair>),
+    Null,
+    // wraps the value of a `return` so that it can bubble up through nested blocks
+    ReturnValue(Box<Object>),
+    // `break` and `continue`, bubble up to the loop they apply to
+    Break,
+    Continue,
+    Error(String),
+    Function(Rc<Function>),
+    Builtin(Builtin),
+
+    // only produced by the compiler and the vm
+    CompiledFunction(Rc<CompiledFunction>),
+    Closure(Rc<Closure>),
+    // variable captured by a closure, shared with the frame it belongs to
+    Cell(Rc<RefCell<Object>>),
+}
+
+impl Object {
+    /// Name of the object type used in error messages
+    pub fn object_type(&self) -> &'static str {
+        return match self {
+            Object::Integer(_) => "INTEGER",
+            Object::Float(_) => "FLOAT",
+            Object::String(_) => "STRING",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::Array(_) => "ARRAY",
+            Object::Hash(_) => "HASH",
+            Object::Null => "NULL",
+            Object::ReturnValue(_) => "RETURN_VALUE",
+            Object::Break => "BREAK",
+            Object::Continue => "CONTINUE",
+            Object::Error(_) => "ERROR",
+            Object::Function(_) => "FUNCTION",
+            Object::Builtin(_) => "BUILTIN",
+            Object::CompiledFunction(_) => "COMPILED_FUNCTION",
+            Object::Closure(_) => "CLOSURE",
+            Object::Cell(_) => "CELL",
+        };
+    }
+
+    pub fn is_error(&self) -> bool {
+        return matches!(self, Object::Error(_));
+    }
+
+    /// Operands of an arithmetic operation (or comparison) involving a float, the other
+    /// operand can be an integer, it is then promoted to a float
+    pub fn float_operands(left: &Object, right: &Object) -> Option<(f64, f64)> {
+        return match (left, right) {
+            (Object::Float(left), Object::Float(right)) => Some((*left, *right)),
+            (Object::Float(left), Object::Integer(right)) => Some((*left, *right as f64)),
+            (Object::Integer(left), Object::Float(right)) => Some((*left as f64, *right)),
+            _ => None,
+        };
+    }
+
+    /// Key of the object in a hash (only integers, booleans and strings can be keys)
+    pub fn hash_key(&self) -> Result<HashKey, String> {
+        return match self {
+            Object::Integer(value) => Ok(HashKey::Integer(*value)),
+            Object::Boolean(value) => Ok(HashKey::Boolean(*value)),
+            Object::String(value) => Ok(HashKey::String(value.clone())),
+            other => Err(format!("unusable as hash key: {}", other.object_type())),
+        };
+    }
+}
+
+// ======================================================
+// Hash Object
+
+/// What a hash is keyed by, two objects with the same value give the same key
+/// NOTE: ordered so that hashes always print in the same order
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HashKey {
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+}
+
+/// Entry of a hash, keeps the original key object around for printing
+#[derive(Debug, Clone, PartialEq)]
+pub struct HashPair {
+    pub key: Object,
+    pub value: Object,
+}
+
+impl Display for Object {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            Object::Integer(value) => write!(f, "{}", value),
+            // the debug format keeps the `.0` of whole numbers
+            Object::Float(value) => write!(f, "{:?}", value),
+            Object::String(value) => write!(f, "{}", value),
+            Object::Boolean(value) => write!(f, "{}", value),
+            Object::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Object::Hash(pairs) => {
+                let pairs: Vec<String> = pairs
+                    .values()
+                    .map(|pair| format!("{}: {}", pair.key, pair.value))
+                    .collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+            Object::Null => write!(f, "null"),
+            Object::ReturnValue(value) => write!(f, "{}", value),
+            Object::Break => write!(f, "break"),
+            Object::Continue => write!(f, "continue"),
+            Object::Error(message) => write!(f, "ERROR: {}", message),
+            Object::Function(function) => write!(f, "{}", function),
+            Object::Builtin(builtin) => write!(f, "builtin function {}", builtin.name),
+            Object::CompiledFunction(function) => write!(f, "CompiledFunction[{:p}]", function),
+            Object::Closure(closure) => write!(f, "Closure[{:p}]", closure),
+            Object::Cell(cell) => write!(f, "{}", cell.borrow()),
+        };
+    }
+}
+
+// ======================================================
+// Function Object
+#[derive(Clone)]
+pub struct Function {
+    pub parameters: Vec<Identifier>,
+    pub body: BlockStatement,
+    pub env: Env, // the environment the function was defined in (closure)
+}
+
+// the environment is left out since it usually contains the function itself
+impl std::fmt::Debug for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return f
+            .debug_struct("Function")
+            .field("parameters", &self.parameters)
+            .field("body", &self.body)
+            .finish_non_exhaustive();
+    }
+}
+
+// two functions are equal only if they were created from the same literal in the same scope
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        return self.parameters == other.parameters
+            && self.body == other.body
+            && Rc::ptr_eq(&self.env, &other.env);
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parameters: Vec<String> = self.parameters.iter().map(|p| p.to_string()).collect();
+
+        // the body prints its own braces
+        return write!(f, "fn({}) {}", parameters.join(", "), self.body.to_string());
+    }
+}
+
+// ======================================================
+// Compiled Function Object
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledFunction {
+    pub instructions: Instructions,
+    pub num_locals: usize, // parameters included
+    pub num_parameters: usize,
+}
+
+// ======================================================
+// Closure Object (compiled function with the variables it captured)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Closure {
+    pub function: Rc<CompiledFunction>,
+    pub free: Vec<Object>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::evaluator::{Environment, eval};
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_function_display() {
+        let tests = [
+            ("fn(x) { x + 1 }", "fn(x) { (x + 1) }"),
+            ("fn(a, b) { let c = a; c }", "fn(a, b) { let c = a; c }"),
+            ("fn() { }", "fn() {}"),
+        ];
+
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(input);
+            let mut parser = Parser::new(&mut lexer);
+            let program = parser.parse_program();
+            assert!(parser.get_errors().is_empty(), "Parser had errors");
+
+            let function = eval(&program, &Environment::new());
+            assert_eq!(
+                function.to_string(),
+                expected,
+                "Wrong display for `{input}`"
+            );
+        }
+    }
+}
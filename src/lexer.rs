@@ -1,18 +1,49 @@
-use crate::token::Token;
+use crate::token::{Span, SpannedToken, Token};
+
+use std::collections::VecDeque;
+
+use unicode_xid::UnicodeXID;
+
+/// Identifiers that are reserved by the language
+pub const KEYWORDS: [(&str, Token); 12] = [
+    ("fn", Token::Function),
+    ("let", Token::Let),
+    ("true", Token::True),
+    ("false", Token::False),
+    ("if", Token::If),
+    ("else", Token::Else),
+    ("return", Token::Return),
+    ("while", Token::While),
+    ("for", Token::For),
+    ("in", Token::In),
+    ("break", Token::Break),
+    ("continue", Token::Continue),
+];
 
 #[derive(Default)]
 pub struct Lexer {
-    input: Vec<u8>,
+    input: String,
+    // byte offsets of `ch` and of the character after it
     position: usize,
     read_position: usize,
-    ch: u8,
+    ch: Option<char>, // None once the end of the input is reached
+
+    // location of `ch` in the input (1-based, counted in characters)
+    line: usize,
+    column: usize,
+
+    // tokens read ahead by peek_nth, handed out before reading new ones
+    lookahead: VecDeque<SpannedToken>,
+    // set once the iterator has returned the Eof token
+    finished: bool,
 }
 
 impl Lexer {
     /// Creates a lexer instance from an input string
     pub fn new(input_string: &str) -> Self {
         let mut lexer = Self {
-            input: input_string.as_bytes().to_vec(),
+            input: input_string.to_string(),
+            line: 1,
             ..Default::default()
         };
 
@@ -21,87 +52,138 @@ impl Lexer {
         return lexer;
     }
 
-    /// Returns the next token in the string
-    pub fn next_token(&mut self) -> Token {
-        self.skip_whitespace();
+    /// Returns the next token in the string along with its location
+    /// (Eof over and over once the end of the input is reached)
+    pub fn next_token(&mut self) -> SpannedToken {
+        if let Some(spanned) = self.lookahead.pop_front() {
+            return spanned;
+        }
+
+        return self.read_spanned_token();
+    }
+
+    /// Token `k` positions ahead, without consuming it (0 is the one next_token returns)
+    pub fn peek_nth(&mut self, k: usize) -> &SpannedToken {
+        while self.lookahead.len() <= k {
+            let spanned = self.read_spanned_token();
+            self.lookahead.push_back(spanned);
+        }
+
+        return &self.lookahead[k];
+    }
+
+    fn read_spanned_token(&mut self) -> SpannedToken {
+        let doc = self.skip_trivia();
+
+        let start = self.position;
+        let line = self.line;
+        let column = self.column;
+
+        let token = self.read_token();
+
+        return SpannedToken {
+            token,
+            span: Span {
+                start,
+                end: self.position,
+                line,
+                column,
+            },
+            doc,
+        };
+    }
+
+    /// Reads the token starting at the current character
+    fn read_token(&mut self) -> Token {
+        let ch = match self.ch {
+            Some(ch) => ch,
+            None => return Token::Eof,
+        };
 
-        let token = match self.ch {
-            0 => Token::Eof,
-            b'a'..=b'z' | b'A'..=b'Z' | b'_' => {
+        let token = match ch {
+            ch if is_identifier_start(ch) => {
                 let identifier = self.read_identifier();
 
                 // early return to not re-read_char
-                return match identifier.as_str() {
-                    "fn" => Token::Function,
-                    "let" => Token::Let,
-                    "true" => Token::True,
-                    "false" => Token::False,
-                    "if" => Token::If,
-                    "else" => Token::Else,
-                    "return" => Token::Return,
-                    _ => Token::Ident(identifier),
+                return match KEYWORDS.iter().find(|(keyword, _)| *keyword == identifier) {
+                    Some((_, token)) => token.clone(),
+                    None => Token::Ident(identifier),
                 };
             }
-            b'0'..=b'9' => return Token::Int(self.read_number()), // early return to not re-read_char
-            b'=' => {
-                if self.peek_char() == b'=' {
-                    self.read_char();
-                    Token::Equal
-                } else {
-                    Token::Assign
-                }
+            '0'..='9' => return self.read_number(), // early return to not re-read_char
+            // `.5` is read as a whole to report the missing digits
+            '.' if self.peek_char().is_some_and(|ch| ch.is_ascii_digit()) => {
+                return self.read_number();
             }
-            b'+' => Token::Plus,
-            b'-' => Token::Minus,
-            b',' => Token::Comma,
-            b'!' => {
-                if self.peek_char() == b'=' {
-                    self.read_char();
-                    Token::NotEqual
-                } else {
-                    Token::Bang
+            '"' => self.read_string(),
+            '=' => self.read_pair('=', Token::Equal, Token::Assign),
+            '+' => self.read_pair('=', Token::PlusAssign, Token::Plus),
+            '-' => self.read_pair('=', Token::MinusAssign, Token::Minus),
+            ',' => Token::Comma,
+            '!' => self.read_pair('=', Token::NotEqual, Token::Bang),
+            '*' => self.read_pair('=', Token::AsteriskAssign, Token::Asterisk),
+            '/' => {
+                // only unterminated block comments are left by skip_trivia
+                if self.peek_char() == Some('*') {
+                    while self.ch.is_some() {
+                        self.read_char();
+                    }
+                    return Token::UnterminatedComment; // early return to not re-read_char
                 }
+                self.read_pair('=', Token::SlashAssign, Token::Slash)
             }
-            b'*' => Token::Asterisk,
-            b'/' => Token::Slash,
-            b'<' => Token::LesserThan,
-            b'>' => Token::GreaterThan,
-            b';' => Token::Semicolon,
-            b'(' => Token::LParen,
-            b')' => Token::RParen,
-            b'{' => Token::LBrace,
-            b'}' => Token::RBrace,
-            _ => {
-                let var = self.ch as char;
-                unreachable!(
-                "You are a bad monkey for trying to fit other characters like `{var}`. Shame on yourself. 🙊"
-            )
-            }
+            '%' => Token::Percent,
+            '<' => self.read_pair('=', Token::LesserEqual, Token::LesserThan),
+            '>' => self.read_pair('=', Token::GreaterEqual, Token::GreaterThan),
+            '&' => self.read_pair('&', Token::And, Token::Illegal(ch.to_string())),
+            '|' => self.read_pair('|', Token::Or, Token::Illegal(ch.to_string())),
+            ';' => Token::Semicolon,
+            ':' => Token::Colon,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '{' => Token::LBrace,
+            '}' => Token::RBrace,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            _ => Token::Illegal(ch.to_string()),
         };
 
         self.read_char();
         return token;
     }
 
+    /// Two-character operator: `pair` if the next character is `second` (ex: `<=`), `single` otherwise
+    fn read_pair(&mut self, second: char, pair: Token, single: Token) -> Token {
+        if self.peek_char() == Some(second) {
+            self.read_char();
+            return pair;
+        }
+
+        return single;
+    }
+
     /// Reads the next character in the input and updates the lexer
     fn read_char(&mut self) {
-        if self.read_position >= self.input.len() {
-            self.ch = 0;
+        // stay put once the end of the input is reached so that Eof keeps the same location
+        if self.read_position > self.input.len() {
+            return;
+        }
+
+        if self.ch == Some('\n') {
+            self.line += 1;
+            self.column = 1;
         } else {
-            self.ch = self.input[self.read_position];
+            self.column += 1;
         }
 
         self.position = self.read_position;
-        self.read_position += 1;
+        self.ch = self.input[self.read_position..].chars().next();
+        self.read_position += self.ch.map_or(1, char::len_utf8);
     }
 
     /// peaks at the next chasracter without advancing the lexer
-    fn peek_char(&mut self) -> u8 {
-        if self.read_position >= self.input.len() {
-            return 0;
-        } else {
-            return self.input[self.read_position];
-        }
+    fn peek_char(&self) -> Option<char> {
+        return self.input.get(self.read_position..)?.chars().next();
     }
 
     /// reads the whole identifier
@@ -109,43 +191,323 @@ impl Lexer {
     fn read_identifier(&mut self) -> String {
         let start_pos = self.position;
 
-        while self.ch.is_ascii_alphabetic() || self.ch == b'_' {
+        while self.ch.is_some_and(is_identifier_continue) {
             self.read_char()
         }
 
-        String::from_utf8_lossy(&self.input[start_pos..self.position]).to_string()
+        self.input[start_pos..self.position].to_string()
     }
 
-    /// reads the whole number
+    /// reads the whole number, an integer (decimal, or with a `0x`, `0o` or `0b` prefix) or a
+    /// decimal float (`3.14`, `1e-9`), `_` separating digits
     /// WARN: you have to skip reading a char after this (or go back a position)
-    fn read_number(&mut self) -> String {
+    fn read_number(&mut self) -> Token {
         let start_pos = self.position;
+        let prefixed = self.ch == Some('0') && matches!(self.peek_char(), Some('x' | 'o' | 'b'));
+
+        self.read_alphanumeric();
+
+        if !prefixed {
+            if self.ch == Some('.') {
+                self.read_char();
+                self.read_alphanumeric();
+            }
+
+            // sign of the exponent
+            if self.input[start_pos..self.position].ends_with(['e', 'E'])
+                && matches!(self.ch, Some('+' | '-'))
+            {
+                self.read_char();
+                self.read_alphanumeric();
+            }
+        }
 
-        while self.ch.is_ascii_digit() {
+        let lexeme = &self.input[start_pos..self.position];
+
+        if !prefixed && lexeme.contains(['.', 'e', 'E']) {
+            return match check_float(lexeme) {
+                Ok(()) => Token::Float(lexeme.to_string()),
+                Err(reason) => Token::InvalidNumber(reason),
+            };
+        }
+
+        return match parse_integer(lexeme) {
+            Ok(value) => Token::Int(value, lexeme.to_string()),
+            Err(reason) => Token::InvalidNumber(reason),
+        };
+    }
+
+    /// letters are part of a number so that `0xfg` or `12ab` are reported as a whole
+    fn read_alphanumeric(&mut self) {
+        while self.ch.is_some_and(is_identifier_continue) {
             self.read_char();
         }
+    }
+
+    /// reads the whole string literal and decodes its escape sequences
+    /// NOTE: stops on the closing quote (or at the end of the input if there is none)
+    fn read_string(&mut self) -> Token {
+        let mut value = String::new();
+        let mut error = None;
 
-        String::from_utf8_lossy(&self.input[start_pos..self.position]).to_string()
+        self.read_char(); // opening quote
+        loop {
+            match self.ch {
+                None => return Token::UnterminatedString,
+                Some('"') => break,
+                Some('\\') => {
+                    self.read_char();
+
+                    let escaped = match self.ch {
+                        None => continue,
+                        Some('n') => Ok('\n'),
+                        Some('t') => Ok('\t'),
+                        Some('r') => Ok('\r'),
+                        Some('0') => Ok('\0'),
+                        Some('"') => Ok('"'),
+                        Some('\\') => Ok('\\'),
+                        Some('u') => self.read_unicode_escape(),
+                        Some(other) => Err(format!("unknown escape sequence `\\{}`", other)),
+                    };
+
+                    match escaped {
+                        Ok(ch) => value.push(ch),
+                        Err(msg) => {
+                            // keep reading up to the closing quote, only the first error is reported
+                            error.get_or_insert(msg);
+                        }
+                    }
+                }
+                Some(ch) => value.push(ch),
+            }
+
+            self.read_char();
+        }
+
+        return match error {
+            Some(msg) => Token::InvalidString(msg),
+            None => Token::String(value),
+        };
     }
 
-    /// skips whitespace
-    fn skip_whitespace(&mut self) {
-        while self.ch.is_ascii_whitespace() {
+    /// reads a `\u{XXXX}` escape sequence (`ch` is on the `u`)
+    /// WARN: leaves `ch` on the last character of the escape sequence
+    fn read_unicode_escape(&mut self) -> Result<char, String> {
+        let start_pos = self.position - 1; // backslash
+
+        if self.peek_char() != Some('{') {
+            return Err("invalid unicode escape `\\u`, expected `\\u{...}`".to_string());
+        }
+        self.read_char();
+
+        let mut digits = String::new();
+        while let Some(ch) = self.peek_char().filter(char::is_ascii_hexdigit) {
+            digits.push(ch);
             self.read_char();
         }
+
+        let invalid = |lexer: &Lexer| {
+            format!(
+                "invalid unicode escape `{}`",
+                &lexer.input[start_pos..lexer.read_position]
+            )
+        };
+
+        if self.peek_char() != Some('}') {
+            return Err(invalid(self));
+        }
+        self.read_char();
+
+        if digits.is_empty() || digits.len() > 6 {
+            return Err(invalid(self));
+        }
+
+        return u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| invalid(self));
+    }
+
+    /// Skips the whitespace and comments in front of the next token
+    /// returns the `///` doc comments found there (one line each, without the slashes)
+    /// NOTE: an unterminated block comment is left for read_token to report
+    fn skip_trivia(&mut self) -> Option<String> {
+        let mut doc: Vec<String> = Vec::new();
+
+        loop {
+            let rest = &self.input[self.position..];
+
+            let end = if self.ch.is_some_and(char::is_whitespace) {
+                self.read_position
+            } else if rest.starts_with("//") {
+                let comment = rest.split('\n').next().unwrap_or(rest);
+
+                // `////` is a regular comment (ex: a line of slashes)
+                if let Some(text) = comment.strip_prefix("///")
+                    && !text.starts_with('/')
+                {
+                    let text = text.trim_end_matches('\r');
+                    doc.push(text.strip_prefix(' ').unwrap_or(text).to_string());
+                }
+
+                self.position + comment.len()
+            } else if rest.starts_with("/*") {
+                match block_comment_len(rest) {
+                    Some(len) => self.position + len,
+                    None => break,
+                }
+            } else {
+                break;
+            };
+
+            while self.position < end {
+                self.read_char();
+            }
+        }
+
+        if doc.is_empty() {
+            return None;
+        }
+
+        return Some(doc.join("\n"));
+    }
+}
+
+/// Length in bytes of the block comment at the start of `input`, None if it is not closed
+/// (block comments nest: `/* a /* b */ c */` is a single comment)
+fn block_comment_len(input: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut position = 0;
+
+    while position < input.len() {
+        let rest = &input[position..];
+
+        if rest.starts_with("/*") {
+            depth += 1;
+            position += 2;
+        } else if rest.starts_with("*/") {
+            depth -= 1;
+            position += 2;
+
+            if depth == 0 {
+                return Some(position);
+            }
+        } else {
+            position += rest.chars().next()?.len_utf8();
+        }
+    }
+
+    return None;
+}
+
+/// Iterates over the tokens of the input, Eof included
+impl Iterator for Lexer {
+    type Item = SpannedToken;
+
+    fn next(&mut self) -> Option<SpannedToken> {
+        if self.finished {
+            return None;
+        }
+
+        let spanned = self.next_token();
+        self.finished = spanned.token == Token::Eof;
+
+        return Some(spanned);
     }
 }
 
+/// Value of an integer literal, None if it does not fit in an i64
+fn parse_integer(lexeme: &str) -> Result<Option<i64>, String> {
+    let (radix, base, digits) = split_radix(lexeme);
+
+    if digits.is_empty() {
+        return Err(format!("missing digits after `{}`", lexeme));
+    }
+
+    check_digits(digits, radix, base)?;
+
+    return Ok(integer_magnitude(lexeme).and_then(|magnitude| i64::try_from(magnitude).ok()));
+}
+
+/// Value of a well formed integer literal as an unsigned number, None if it does not fit in a u64
+/// (the parser uses it for `-9223372036854775808`, whose literal is out of range of an i64)
+pub fn integer_magnitude(lexeme: &str) -> Option<u64> {
+    let (radix, _, digits) = split_radix(lexeme);
+
+    return u64::from_str_radix(&digits.replace('_', ""), radix).ok();
+}
+
+/// Radix, name of the base and digits of an integer literal
+fn split_radix(lexeme: &str) -> (u32, &'static str, &str) {
+    return match lexeme.get(..2) {
+        Some("0x") => (16, "hexadecimal", &lexeme[2..]),
+        Some("0o") => (8, "octal", &lexeme[2..]),
+        Some("0b") => (2, "binary", &lexeme[2..]),
+        _ => (10, "decimal", lexeme),
+    };
+}
+
+/// Checks that a float literal is well formed, its value is computed by the parser
+fn check_float(lexeme: &str) -> Result<(), String> {
+    let (mantissa, exponent) = match lexeme.find(['e', 'E']) {
+        Some(index) => (&lexeme[..index], Some(&lexeme[index + 1..])),
+        None => (lexeme, None),
+    };
+
+    let (integer, fraction) = match mantissa.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (mantissa, None),
+    };
+    let exponent = exponent.map(|exponent| exponent.strip_prefix(['+', '-']).unwrap_or(exponent));
+
+    if integer.is_empty() {
+        return Err("missing digits before `.`".to_string());
+    }
+    if fraction == Some("") {
+        return Err("missing digits after `.`".to_string());
+    }
+    if exponent == Some("") {
+        return Err("missing digits in the exponent".to_string());
+    }
+
+    for digits in [Some(integer), fraction, exponent].into_iter().flatten() {
+        check_digits(digits, 10, "float")?;
+    }
+
+    return Ok(());
+}
+
+fn check_digits(digits: &str, radix: u32, base: &str) -> Result<(), String> {
+    if let Some(invalid) = digits.chars().find(|ch| *ch != '_' && !ch.is_digit(radix)) {
+        return Err(format!("invalid digit `{}` in {} literal", invalid, base));
+    }
+
+    if digits.starts_with('_') || digits.ends_with('_') || digits.contains("__") {
+        return Err("`_` can only separate digits".to_string());
+    }
+
+    return Ok(());
+}
+
+/// Identifiers follow Unicode's XID rules, plus a leading `_`
+pub fn is_identifier_start(ch: char) -> bool {
+    return ch == '_' || UnicodeXID::is_xid_start(ch);
+}
+
+pub fn is_identifier_continue(ch: char) -> bool {
+    return UnicodeXID::is_xid_continue(ch);
+}
+
 // ==================================================================
 // TEST
 
 #[cfg(test)]
 mod tests {
-    use super::{Lexer, Token};
+    use super::{Lexer, Span, SpannedToken, Token};
 
     #[test]
     fn token_recognition_single_char() {
-        let test_string = ")(){}{}+,;;=";
+        let test_string = ")(){}{}+,;;=[]:";
 
         let expected_output = [
             Token::RParen,
@@ -160,13 +522,16 @@ mod tests {
             Token::Semicolon,
             Token::Semicolon,
             Token::Assign,
+            Token::LBracket,
+            Token::RBracket,
+            Token::Colon,
             Token::Eof,
         ];
 
-        let mut lexer = Lexer::new(&test_string);
+        let mut lexer = Lexer::new(test_string);
 
         for (i, expected) in expected_output.iter().enumerate() {
-            let generated = lexer.next_token();
+            let generated = lexer.next_token().token;
             assert_eq!(
                 generated, *expected,
                 "Wrong token generated at character position `{i}` (expected: `{expected}`, generated: `{generated}`)"
@@ -184,7 +549,7 @@ let add = fn(x, y) {
 };
 
 let result = add(five, ten);
-!-/*5;
+!-/ *5;
 5 < 10 > 5;
 
 if (5 < 10) {
@@ -200,12 +565,12 @@ if (5 < 10) {
             Token::Let,
             Token::Ident("five".to_string()),
             Token::Assign,
-            Token::Int("5".to_string()),
+            Token::Int(Some(5), "5".to_string()),
             Token::Semicolon,
             Token::Let,
             Token::Ident("ten".to_string()),
             Token::Assign,
-            Token::Int("10".to_string()),
+            Token::Int(Some(10), "10".to_string()),
             Token::Semicolon,
             Token::Let,
             Token::Ident("add".to_string()),
@@ -237,19 +602,19 @@ if (5 < 10) {
             Token::Minus,
             Token::Slash,
             Token::Asterisk,
-            Token::Int("5".to_string()),
+            Token::Int(Some(5), "5".to_string()),
             Token::Semicolon,
-            Token::Int("5".to_string()),
+            Token::Int(Some(5), "5".to_string()),
             Token::LesserThan,
-            Token::Int("10".to_string()),
+            Token::Int(Some(10), "10".to_string()),
             Token::GreaterThan,
-            Token::Int("5".to_string()),
+            Token::Int(Some(5), "5".to_string()),
             Token::Semicolon,
             Token::If,
             Token::LParen,
-            Token::Int("5".to_string()),
+            Token::Int(Some(5), "5".to_string()),
             Token::LesserThan,
-            Token::Int("10".to_string()),
+            Token::Int(Some(10), "10".to_string()),
             Token::RParen,
             Token::LBrace,
             Token::Return,
@@ -262,25 +627,458 @@ if (5 < 10) {
             Token::False,
             Token::Semicolon,
             Token::RBrace,
-            Token::Int("10".to_string()),
+            Token::Int(Some(10), "10".to_string()),
             Token::Equal,
-            Token::Int("10".to_string()),
+            Token::Int(Some(10), "10".to_string()),
             Token::Semicolon,
-            Token::Int("10".to_string()),
+            Token::Int(Some(10), "10".to_string()),
             Token::NotEqual,
-            Token::Int("9".to_string()),
+            Token::Int(Some(9), "9".to_string()),
             Token::Semicolon,
             Token::Eof,
         ];
 
-        let mut lexer = Lexer::new(&test_string);
+        let mut lexer = Lexer::new(test_string);
 
         for (i, expected) in expected_output.iter().enumerate() {
-            let generated = lexer.next_token();
+            let generated = lexer.next_token().token;
             assert_eq!(
                 generated, *expected,
                 "Wrong token generated at character position `{i}` (expected: `{expected}`, generated: `{generated}`)"
             );
         }
     }
+
+    #[test]
+    fn loop_keywords() {
+        let test_string = "while (x) { break; } for (y in ys) { continue; } x = inner;";
+
+        let expected_output = [
+            Token::While,
+            Token::LParen,
+            Token::Ident("x".to_string()),
+            Token::RParen,
+            Token::LBrace,
+            Token::Break,
+            Token::Semicolon,
+            Token::RBrace,
+            Token::For,
+            Token::LParen,
+            Token::Ident("y".to_string()),
+            Token::In,
+            Token::Ident("ys".to_string()),
+            Token::RParen,
+            Token::LBrace,
+            Token::Continue,
+            Token::Semicolon,
+            Token::RBrace,
+            Token::Ident("x".to_string()),
+            Token::Assign,
+            Token::Ident("inner".to_string()),
+            Token::Semicolon,
+            Token::Eof,
+        ];
+
+        let generated: Vec<Token> = Lexer::new(test_string)
+            .map(|spanned| spanned.token)
+            .collect();
+        assert_eq!(generated, expected_output);
+    }
+
+    #[test]
+    fn two_character_operators() {
+        let test_string = "a <= b >= c % d && e || f; x += 1 -= 2 *= 3 /= 4 < > & | =";
+
+        let expected_output = [
+            Token::Ident("a".to_string()),
+            Token::LesserEqual,
+            Token::Ident("b".to_string()),
+            Token::GreaterEqual,
+            Token::Ident("c".to_string()),
+            Token::Percent,
+            Token::Ident("d".to_string()),
+            Token::And,
+            Token::Ident("e".to_string()),
+            Token::Or,
+            Token::Ident("f".to_string()),
+            Token::Semicolon,
+            Token::Ident("x".to_string()),
+            Token::PlusAssign,
+            Token::Int(Some(1), "1".to_string()),
+            Token::MinusAssign,
+            Token::Int(Some(2), "2".to_string()),
+            Token::AsteriskAssign,
+            Token::Int(Some(3), "3".to_string()),
+            Token::SlashAssign,
+            Token::Int(Some(4), "4".to_string()),
+            Token::LesserThan,
+            Token::GreaterThan,
+            Token::Illegal("&".to_string()),
+            Token::Illegal("|".to_string()),
+            Token::Assign,
+            Token::Eof,
+        ];
+
+        let generated: Vec<Token> = Lexer::new(test_string)
+            .map(|spanned| spanned.token)
+            .collect();
+        assert_eq!(generated, expected_output);
+    }
+
+    #[test]
+    fn integer_literals() {
+        let tests = [
+            ("0", Token::Int(Some(0), "0".to_string())),
+            (
+                "1_000_000",
+                Token::Int(Some(1_000_000), "1_000_000".to_string()),
+            ),
+            ("0xFF", Token::Int(Some(255), "0xFF".to_string())),
+            (
+                "0x7fff_ffff",
+                Token::Int(Some(0x7fff_ffff), "0x7fff_ffff".to_string()),
+            ),
+            ("0o17", Token::Int(Some(15), "0o17".to_string())),
+            (
+                "0b1010_0101",
+                Token::Int(Some(165), "0b1010_0101".to_string()),
+            ),
+            (
+                "9223372036854775807",
+                Token::Int(Some(i64::MAX), "9223372036854775807".to_string()),
+            ),
+            // out of range, reported by the parser
+            (
+                "9223372036854775808",
+                Token::Int(None, "9223372036854775808".to_string()),
+            ),
+            (
+                "0x1_0000_0000_0000_0000",
+                Token::Int(None, "0x1_0000_0000_0000_0000".to_string()),
+            ),
+            (
+                "0x",
+                Token::InvalidNumber("missing digits after `0x`".to_string()),
+            ),
+            (
+                "0xfg",
+                Token::InvalidNumber("invalid digit `g` in hexadecimal literal".to_string()),
+            ),
+            (
+                "0o8",
+                Token::InvalidNumber("invalid digit `8` in octal literal".to_string()),
+            ),
+            (
+                "0b102",
+                Token::InvalidNumber("invalid digit `2` in binary literal".to_string()),
+            ),
+            (
+                "12ab",
+                Token::InvalidNumber("invalid digit `a` in decimal literal".to_string()),
+            ),
+            (
+                "1__0",
+                Token::InvalidNumber("`_` can only separate digits".to_string()),
+            ),
+            (
+                "10_",
+                Token::InvalidNumber("`_` can only separate digits".to_string()),
+            ),
+            (
+                "0x_1",
+                Token::InvalidNumber("`_` can only separate digits".to_string()),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let tokens: Vec<SpannedToken> = Lexer::new(input).collect();
+
+            assert_eq!(tokens[0].token, expected, "Wrong token for `{input}`");
+            assert_eq!(
+                tokens[0].span.end,
+                input.len(),
+                "`{input}` is a single token"
+            );
+            assert_eq!(tokens[1].token, Token::Eof);
+        }
+    }
+
+    #[test]
+    fn float_literals() {
+        let invalid = |reason: &str| Token::InvalidNumber(reason.to_string());
+
+        let tests = [
+            ("3.14", Token::Float("3.14".to_string())),
+            ("1e-9", Token::Float("1e-9".to_string())),
+            ("2.5E+3", Token::Float("2.5E+3".to_string())),
+            ("0e0", Token::Float("0e0".to_string())),
+            ("1_000.000_1", Token::Float("1_000.000_1".to_string())),
+            (".5", invalid("missing digits before `.`")),
+            ("1.", invalid("missing digits after `.`")),
+            ("1.e3", invalid("missing digits after `.`")),
+            ("1e", invalid("missing digits in the exponent")),
+            ("1e+", invalid("missing digits in the exponent")),
+            ("1.5x", invalid("invalid digit `x` in float literal")),
+            ("1e5e3", invalid("invalid digit `e` in float literal")),
+            ("1._5", invalid("`_` can only separate digits")),
+        ];
+
+        for (input, expected) in tests {
+            let tokens: Vec<SpannedToken> = Lexer::new(input).collect();
+
+            assert_eq!(tokens[0].token, expected, "Wrong token for `{input}`");
+            assert_eq!(
+                tokens[0].span.end,
+                input.len(),
+                "`{input}` is a single token"
+            );
+            assert_eq!(tokens[1].token, Token::Eof);
+        }
+
+        // a prefixed integer has no fraction nor exponent
+        let tokens: Vec<Token> = Lexer::new("0x1e-1 1.2.3 x.y")
+            .map(|spanned| spanned.token)
+            .collect();
+        assert_eq!(
+            tokens,
+            [
+                Token::Int(Some(0x1e), "0x1e".to_string()),
+                Token::Minus,
+                Token::Int(Some(1), "1".to_string()),
+                Token::Float("1.2".to_string()),
+                invalid("missing digits before `.`"),
+                Token::Ident("x".to_string()),
+                Token::Illegal(".".to_string()),
+                Token::Ident("y".to_string()),
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn illegal_characters() {
+        let test_string = "let @ = 5 # 3;\n€";
+
+        let expected_output = [
+            (Token::Let, 0, 3),
+            (Token::Illegal("@".to_string()), 4, 5),
+            (Token::Assign, 6, 7),
+            (Token::Int(Some(5), "5".to_string()), 8, 9),
+            (Token::Illegal("#".to_string()), 10, 11),
+            (Token::Int(Some(3), "3".to_string()), 12, 13),
+            (Token::Semicolon, 13, 14),
+            (Token::Illegal("€".to_string()), 15, 18),
+            (Token::Eof, 18, 18),
+        ];
+
+        let mut lexer = Lexer::new(test_string);
+
+        for (token, start, end) in expected_output {
+            let generated = lexer.next_token();
+            assert_eq!(generated.token, token);
+            assert_eq!(
+                (generated.span.start, generated.span.end),
+                (start, end),
+                "Wrong span for `{token}`"
+            );
+        }
+    }
+
+    #[test]
+    fn unicode_input() {
+        let test_string = "let café = 5;\nnaïve_1 + 🙈\0x";
+
+        let expected_output = [
+            (Token::Let, 0, 3, 1, 1),
+            (Token::Ident("café".to_string()), 4, 9, 1, 5),
+            (Token::Assign, 10, 11, 1, 10),
+            (Token::Int(Some(5), "5".to_string()), 12, 13, 1, 12),
+            (Token::Semicolon, 13, 14, 1, 13),
+            (Token::Ident("naïve_1".to_string()), 15, 23, 2, 1),
+            (Token::Plus, 24, 25, 2, 9),
+            (Token::Illegal("🙈".to_string()), 26, 30, 2, 11),
+            (Token::Illegal("\0".to_string()), 30, 31, 2, 12),
+            (Token::Ident("x".to_string()), 31, 32, 2, 13),
+            (Token::Eof, 32, 32, 2, 14),
+        ];
+
+        let mut lexer = Lexer::new(test_string);
+
+        for (token, start, end, line, column) in expected_output {
+            let generated = lexer.next_token();
+            assert_eq!(generated.token, token);
+            assert_eq!(
+                generated.span,
+                Span {
+                    start,
+                    end,
+                    line,
+                    column
+                },
+                "Wrong span for `{token}`"
+            );
+        }
+    }
+
+    #[test]
+    fn string_litterals() {
+        let tests = [
+            (r#""foobar""#, Token::String("foobar".to_string())),
+            (r#""foo bar""#, Token::String("foo bar".to_string())),
+            (r#""""#, Token::String(String::new())),
+            (
+                r#""a\nb\tc\"d\\e""#,
+                Token::String("a\nb\tc\"d\\e".to_string()),
+            ),
+            (r#""\u{1F648} \u{e9}""#, Token::String("🙈 é".to_string())),
+            (r#""foo"#, Token::UnterminatedString),
+            (r#""foo\""#, Token::UnterminatedString),
+            (
+                r#""a\qb""#,
+                Token::InvalidString("unknown escape sequence `\\q`".to_string()),
+            ),
+            (
+                r#""\u{110000}""#,
+                Token::InvalidString("invalid unicode escape `\\u{110000}`".to_string()),
+            ),
+            (
+                r#""\u{12""#,
+                Token::InvalidString("invalid unicode escape `\\u{12`".to_string()),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(input);
+            assert_eq!(
+                lexer.next_token().token,
+                expected,
+                "Wrong token for `{input}`"
+            );
+        }
+
+        // the whole string is consumed, even when it is invalid
+        let mut lexer = Lexer::new(r#""a\qb" + "c";"#);
+        let tokens: Vec<Token> = (0..4).map(|_| lexer.next_token().token).collect();
+        assert_eq!(
+            tokens,
+            [
+                Token::InvalidString("unknown escape sequence `\\q`".to_string()),
+                Token::Plus,
+                Token::String("c".to_string()),
+                Token::Semicolon,
+            ]
+        );
+    }
+
+    #[test]
+    fn token_spans() {
+        let test_string = "let x = 5;\n  x == 10\n";
+
+        let expected_output = [
+            (Token::Let, 0, 3, 1, 1),
+            (Token::Ident("x".to_string()), 4, 5, 1, 5),
+            (Token::Assign, 6, 7, 1, 7),
+            (Token::Int(Some(5), "5".to_string()), 8, 9, 1, 9),
+            (Token::Semicolon, 9, 10, 1, 10),
+            (Token::Ident("x".to_string()), 13, 14, 2, 3),
+            (Token::Equal, 15, 17, 2, 5),
+            (Token::Int(Some(10), "10".to_string()), 18, 20, 2, 8),
+            (Token::Eof, 21, 21, 3, 1),
+            (Token::Eof, 21, 21, 3, 1),
+        ];
+
+        let mut lexer = Lexer::new(test_string);
+
+        for (token, start, end, line, column) in expected_output {
+            let generated = lexer.next_token();
+            assert_eq!(generated.token, token);
+            assert_eq!(
+                generated.span,
+                Span {
+                    start,
+                    end,
+                    line,
+                    column
+                },
+                "Wrong span for `{token}`"
+            );
+        }
+    }
+
+    #[test]
+    fn iterator_and_lookahead() {
+        let mut lexer = Lexer::new("let x = 5;");
+
+        assert_eq!(lexer.peek_nth(2).token, Token::Assign);
+        assert_eq!(lexer.peek_nth(0).token, Token::Let);
+        assert_eq!(lexer.peek_nth(10).token, Token::Eof);
+
+        assert_eq!(lexer.next_token().token, Token::Let);
+        assert_eq!(lexer.peek_nth(0).token, Token::Ident("x".to_string()));
+
+        let tokens: Vec<Token> = lexer.map(|spanned| spanned.token).collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Ident("x".to_string()),
+                Token::Assign,
+                Token::Int(Some(5), "5".to_string()),
+                Token::Semicolon,
+                Token::Eof,
+            ]
+        );
+
+        let spans: Vec<(usize, usize)> = Lexer::new("a  bc")
+            .map(|spanned| (spanned.span.start, spanned.span.end))
+            .collect();
+        assert_eq!(spans, vec![(0, 1), (3, 5), (5, 5)]);
+    }
+
+    #[test]
+    fn comments() {
+        let input = "1 // one
+/* block /* nested */ still a comment */ 2 /**/ /
+//// not a doc
+/// Adds two numbers
+///
+///   `a` and `b`
+let
+/// unused /* not nested
+3 /* 4 /* 5 */";
+
+        let tokens: Vec<(Token, Option<String>)> = Lexer::new(input)
+            .map(|spanned| (spanned.token, spanned.doc))
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                (Token::Int(Some(1), "1".to_string()), None),
+                (Token::Int(Some(2), "2".to_string()), None),
+                (Token::Slash, None),
+                (
+                    Token::Let,
+                    Some("Adds two numbers\n\n  `a` and `b`".to_string())
+                ),
+                (
+                    Token::Int(Some(3), "3".to_string()),
+                    Some("unused /* not nested".to_string())
+                ),
+                (Token::UnterminatedComment, None),
+                (Token::Eof, None),
+            ]
+        );
+
+        let mut lexer = Lexer::new("a /* é */ b /* c");
+        let spans: Vec<(usize, usize, usize)> = (0..4)
+            .map(|_| {
+                let span = lexer.next_token().span;
+                (span.start, span.end, span.column)
+            })
+            .collect();
+        assert_eq!(
+            spans,
+            vec![(0, 1, 1), (11, 12, 11), (13, 17, 13), (17, 17, 17)]
+        );
+    }
 }
@@ -1,49 +1,31 @@
+// explicit returns are the house style
+#![allow(clippy::needless_return)]
+
 pub mod ast;
+pub mod cli;
+pub mod code;
+pub mod compiler;
+pub mod diagnostic;
+pub mod evaluator;
 pub mod lexer;
 pub mod parser;
+pub mod repl;
 pub mod token;
+pub mod vm;
 
-use crate::lexer::Lexer;
-use crate::token::Token;
-
-use rustyline::error::ReadlineError;
-use rustyline::{DefaultEditor, Result};
-
-const PROMPT: &str = ">> ";
+use std::process::ExitCode;
 
-fn main() -> Result<()> {
-    let mut rl = DefaultEditor::new()?;
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
 
-    println!("Hello! This is the Monkey Programming Language!");
-    println!("Feel free to type in commands.");
-
-    loop {
-        let readline = rl.readline(PROMPT);
-        match readline {
-            Ok(line) => {
-                let mut lexer = Lexer::new(line.as_str());
-
-                let mut token = lexer.next_token();
-                while token != Token::Eof {
-                    print!("{token}, ");
-                    token = lexer.next_token();
-                }
-                print!("{token}\n");
-            }
-            Err(ReadlineError::Interrupted) => {
-                println!("CTRL-C");
-                break;
-            }
-            Err(ReadlineError::Eof) => {
-                println!("CTRL-D");
-                break;
-            }
-            Err(err) => {
-                println!("Error: {:?}", err);
-                break;
-            }
+    return match cli::parse_args(&args) {
+        Ok(command) => cli::execute(command),
+        Err(message) => {
+            eprintln!(
+                "error: {}\n\nRun `compiler --help` for the list of commands",
+                message
+            );
+            ExitCode::from(cli::EXIT_USAGE)
         }
-    }
-
-    Ok(())
+    };
 }
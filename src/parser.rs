@@ -1,290 +1,2412 @@
 use crate::ast::*;
-use crate::lexer::Lexer;
-use crate::token::Token;
+use crate::diagnostic::Diagnostic;
+use crate::lexer::{Lexer, integer_magnitude};
+use crate::token::{Span, SpannedToken, Token};
 use std::collections::HashMap;
+use std::mem::{Discriminant, discriminant};
 
-type PrefixParseFn = fn() -> Box<dyn Expression>;
-type InfixParseFn = fn(Box<dyn Expression>) -> Box<dyn Expression>;
+type PrefixParseFn<'a> = fn(&mut Parser<'a>) -> Option<Expression>;
+type InfixParseFn<'a> = fn(&mut Parser<'a>, Expression) -> Option<Expression>;
 
-struct Parser<'a> {
+// Error codes of the diagnostics
+pub const UNEXPECTED_TOKEN: &str = "E0001";
+pub const EXPECTED_EXPRESSION: &str = "E0002";
+pub const INVALID_NUMBER: &str = "E0003";
+pub const ILLEGAL_CHARACTER: &str = "E0004";
+pub const INVALID_STRING: &str = "E0005";
+pub const UNTERMINATED_COMMENT: &str = "E0006";
+pub const OUTSIDE_LOOP: &str = "E0007";
+pub const LOOP_CONTROL_IN_EXPRESSION: &str = "E0008";
+pub const NESTED_TOO_DEEPLY: &str = "E0009";
+
+// past this many errors, the following ones are most likely noise
+const MAX_ERRORS: usize = 20;
+
+// the parser (and everything walking the tree) recurses once per level
+const MAX_NESTING: usize = 256;
+
+/// Binding power of the operators, from weakest to strongest
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Precedence {
+    Lowest,
+    Or,          // ||
+    And,         // &&
+    Equals,      // ==
+    LessGreater, // > or <
+    Sum,         // +
+    Product,     // *
+    Prefix,      // -x or !x
+    Call,        // my_function(x)
+    Index,       // my_array[x]
+}
+
+impl Precedence {
+    /// Precedence table of the infix operators
+    pub fn of(token: &Token) -> Self {
+        return match token {
+            Token::Or => Precedence::Or,
+            Token::And => Precedence::And,
+            Token::Equal | Token::NotEqual => Precedence::Equals,
+            Token::LesserThan | Token::GreaterThan | Token::LesserEqual | Token::GreaterEqual => {
+                Precedence::LessGreater
+            }
+            Token::Plus | Token::Minus => Precedence::Sum,
+            Token::Asterisk | Token::Slash | Token::Percent => Precedence::Product,
+            Token::LParen => Precedence::Call,
+            Token::LBracket => Precedence::Index,
+            _ => Precedence::Lowest,
+        };
+    }
+}
+
+pub struct Parser<'a> {
     lexer: &'a mut Lexer,
 
+    // the tokens after cur_token are looked at through the lookahead of the lexer (see peek)
     cur_token: Token,
-    peek_token: Token,
+    cur_span: Span,
+    cur_doc: Option<String>, // doc comments of cur_token
+
+    // keyed on the token variant so that `Ident("x")` and `Ident("y")` share a parse function
+    prefix_parse_fns: HashMap<Discriminant<Token>, PrefixParseFn<'a>>,
+    infix_parse_fns: HashMap<Discriminant<Token>, InfixParseFn<'a>>,
+
+    errors: Vec<Diagnostic>,
 
-    prefix_parse_fns: HashMap<Token, &'a PrefixParseFn>,
-    infix_parse_fns: HashMap<Token, &'a InfixParseFn>,
+    // number of blocks the current token is in, a `}` only ends a broken statement inside a block
+    block_depth: usize,
 
-    errors: Vec<String>,
+    // number of loops the current token is in (within the current function)
+    loop_depth: usize,
+
+    // number of expressions being parsed the current token is in
+    expression_depth: usize,
 }
 
 impl<'a> Parser<'a> {
-    fn new(lexer: &'a mut Lexer) -> Self {
+    pub fn new(lexer: &'a mut Lexer) -> Self {
         let mut parser = Parser {
             lexer,
-            cur_token: Token::Illegal,
-            peek_token: Token::Illegal,
+            cur_token: Token::Eof,
+            cur_span: Span::default(),
+            cur_doc: None,
             errors: Vec::new(),
+            block_depth: 0,
+            loop_depth: 0,
+            expression_depth: 0,
             prefix_parse_fns: HashMap::new(),
             infix_parse_fns: HashMap::new(),
         };
 
-        parser.next_token();
+        parser.register_prefix(Token::Ident(String::new()), Parser::parse_identifier);
+        parser.register_prefix(
+            Token::Int(None, String::new()),
+            Parser::parse_integer_literal,
+        );
+        parser.register_prefix(Token::Float(String::new()), Parser::parse_float_literal);
+        parser.register_prefix(Token::String(String::new()), Parser::parse_string_literal);
+        parser.register_prefix(Token::True, Parser::parse_boolean);
+        parser.register_prefix(Token::False, Parser::parse_boolean);
+        parser.register_prefix(Token::Bang, Parser::parse_prefix_expression);
+        parser.register_prefix(Token::Minus, Parser::parse_prefix_expression);
+        parser.register_prefix(Token::LParen, Parser::parse_grouped_expression);
+        parser.register_prefix(Token::If, Parser::parse_if_expression);
+        parser.register_prefix(Token::Function, Parser::parse_function_literal);
+        parser.register_prefix(Token::LBracket, Parser::parse_array_literal);
+        parser.register_prefix(Token::LBrace, Parser::parse_hash_literal);
+
+        for operator in [
+            Token::Plus,
+            Token::Minus,
+            Token::Asterisk,
+            Token::Slash,
+            Token::Percent,
+            Token::Equal,
+            Token::NotEqual,
+            Token::LesserThan,
+            Token::GreaterThan,
+            Token::LesserEqual,
+            Token::GreaterEqual,
+            Token::And,
+            Token::Or,
+        ] {
+            parser.register_infix(operator, Parser::parse_infix_expression);
+        }
+        parser.register_infix(Token::LParen, Parser::parse_call_expression);
+        parser.register_infix(Token::LBracket, Parser::parse_index_expression);
+
         parser.next_token();
 
         return parser;
     }
 
-    fn parse_program(&mut self) -> Program {
+    pub fn parse_program(&mut self) -> Program {
         let mut program = Program {
             statements: Vec::new(),
+            span: self.cur_span,
         };
 
         while self.cur_token != Token::Eof {
-            let poss_statement = self.parse_statement();
-
-            if let Some(statement) = poss_statement {
-                program.statements.push(statement);
-            }
+            let statement = self.parse_statement_or_recover();
+            program.statements.push(statement);
 
             self.next_token();
         }
 
+        program.span = program.span.to(self.cur_span);
+
         return program;
     }
 
-    fn parse_statement(&mut self) -> Option<Box<dyn Statement>> {
-        match self.cur_token {
-            Token::Let => {
-                let let_statement = self.parse_let_statement()?;
-                return Some(Box::new(let_statement) as Box<dyn Statement>);
+    /// parses a statement, or skips the tokens of a broken one and returns an error node
+    fn parse_statement_or_recover(&mut self) -> Statement {
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+
+        if let Some(statement) = self.parse_statement() {
+            return statement;
+        }
+
+        let end = self.synchronize();
+
+        return Statement::Error(ErrorStatement {
+            token,
+            span: start.to(end),
+        });
+    }
+
+    /// Panic-mode recovery: skips the tokens up to the end of the broken statement (a `;`,
+    /// or right before a keyword starting a statement or the `}` closing the current block)
+    /// cur_token is left on the last skipped token, whose span is returned
+    fn synchronize(&mut self) -> Span {
+        loop {
+            if matches!(self.cur_token, Token::Semicolon | Token::Eof) {
+                return self.cur_span;
             }
-            Token::Return => {
-                let return_statement = self.parse_return_statement()?;
-                return Some(Box::new(return_statement) as Box<dyn Statement>);
+
+            // the error was on the `}` closing the block, it is left to parse_block_statement
+            if self.block_depth > 0 && self.cur_token_is(&Token::RBrace) {
+                return self.cur_span;
             }
-            _ => return None,
+
+            let next_statement = match self.peek().token {
+                Token::Let
+                | Token::Return
+                | Token::While
+                | Token::For
+                | Token::Break
+                | Token::Continue
+                | Token::Eof => true,
+                // a stray `}` outside of a block is skipped with the statement
+                Token::RBrace => self.block_depth > 0,
+                _ => false,
+            };
+
+            if next_statement {
+                return self.cur_span;
+            }
+
+            self.next_token();
         }
     }
 
+    fn parse_statement(&mut self) -> Option<Statement> {
+        let assignment = is_assignment(&self.peek().token);
+
+        return match self.cur_token {
+            Token::Let => Some(Statement::Let(self.parse_let_statement()?)),
+            Token::Return => Some(Statement::Return(self.parse_return_statement()?)),
+            Token::Ident(_) if assignment => {
+                Some(Statement::Assign(self.parse_assign_statement()?))
+            }
+            Token::While => Some(Statement::While(self.parse_while_statement()?)),
+            Token::For => Some(Statement::For(self.parse_for_statement()?)),
+            Token::Break | Token::Continue => Some(self.parse_loop_control()),
+            _ => Some(Statement::Expression(self.parse_expression_statement()?)),
+        };
+    }
+
     fn parse_let_statement(&mut self) -> Option<LetStatement> {
         let let_token = self.cur_token.clone();
+        let start = self.cur_span;
+        let doc = self.cur_doc.clone();
 
         if !self.expect_peek(Token::Ident(String::new())) {
             return None;
         }
 
-        let identifier = match &self.cur_token {
-            Token::Ident(ident_name) => Identifier {
-                token: self.cur_token.clone(),
-                value: ident_name.to_string(),
-            },
-            _ => return None,
-        };
+        let identifier = self.cur_identifier()?;
 
         if !self.expect_peek(Token::Assign) {
             return None;
         }
 
-        // TODO: we need to parse the value of the statement
-        while !self.cur_token_is(&Token::Semicolon) {
+        self.next_token();
+
+        let value = self.parse_expression(Precedence::Lowest)?;
+        self.check_loop_control(&value);
+
+        // the semicolon is optional
+        if self.peek_token_is(&Token::Semicolon) {
             self.next_token();
         }
 
-        let temp_value = Identifier {
-            token: Token::Int("5".to_string()),
-            value: "5".to_string(),
-        };
-
         return Some(LetStatement {
             token: let_token,
             identifier,
-            value: Box::new(temp_value),
+            value,
+            doc,
+            span: start.to(self.cur_span),
         });
     }
 
     fn parse_return_statement(&mut self) -> Option<ReturnStatement> {
         let return_statement = self.cur_token.clone();
+        let start = self.cur_span;
 
         self.next_token();
 
-        // TODO: we need to parse the value of the statement
-        while self.cur_token != Token::Semicolon {
+        let return_value = self.parse_expression(Precedence::Lowest)?;
+        self.check_loop_control(&return_value);
+
+        // the semicolon is optional
+        if self.peek_token_is(&Token::Semicolon) {
             self.next_token();
         }
 
-        let temp_value = Identifier {
-            token: Token::Int("5".to_string()),
-            value: "5".to_string(),
-        };
-
         return Some(ReturnStatement {
             token: return_statement,
-            return_value: Box::new(temp_value),
+            return_value,
+            span: start.to(self.cur_span),
         });
     }
 
-    fn cur_token_is(&self, expected_tok: &Token) -> bool {
-        return std::mem::discriminant(&self.cur_token) == std::mem::discriminant(expected_tok);
+    fn parse_assign_statement(&mut self) -> Option<AssignStatement> {
+        let start = self.cur_span;
+        let identifier = self.cur_identifier()?;
+
+        self.next_token();
+        let token = self.cur_token.clone();
+
+        self.next_token();
+        let value = self.parse_expression(Precedence::Lowest)?;
+        self.check_loop_control(&value);
+
+        // the semicolon is optional
+        if self.peek_token_is(&Token::Semicolon) {
+            self.next_token();
+        }
+
+        return Some(AssignStatement {
+            token,
+            identifier,
+            value,
+            span: start.to(self.cur_span),
+        });
     }
 
-    fn peek_token_is(&self, expected_tok: &Token) -> bool {
-        return std::mem::discriminant(&self.peek_token) == std::mem::discriminant(expected_tok);
+    fn parse_while_statement(&mut self) -> Option<WhileStatement> {
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+
+        if !self.expect_peek(Token::LParen) {
+            return None;
+        }
+        let open = self.cur_span;
+
+        self.next_token();
+        let condition = self.parse_expression(Precedence::Lowest)?;
+        self.check_loop_control(&condition);
+
+        if !self.expect_closing(Token::RParen, open) {
+            return None;
+        }
+
+        if !self.expect_peek(Token::LBrace) {
+            return None;
+        }
+
+        let body = self.parse_loop_body();
+
+        return Some(WhileStatement {
+            token,
+            condition,
+            body,
+            span: start.to(self.cur_span),
+        });
     }
 
-    fn expect_peek(&mut self, expected_tok: Token) -> bool {
-        if self.peek_token_is(&expected_tok) {
-            self.next_token();
-            return true;
-        } else {
-            self.peek_error(expected_tok);
-            return false;
+    fn parse_for_statement(&mut self) -> Option<ForStatement> {
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+
+        if !self.expect_peek(Token::LParen) {
+            return None;
+        }
+        let open = self.cur_span;
+
+        if !self.expect_peek(Token::Ident(String::new())) {
+            return None;
+        }
+        let variable = self.cur_identifier()?;
+
+        if !self.expect_peek(Token::In) {
+            return None;
+        }
+
+        self.next_token();
+        let iterable = self.parse_expression(Precedence::Lowest)?;
+        self.check_loop_control(&iterable);
+
+        if !self.expect_closing(Token::RParen, open) {
+            return None;
         }
+
+        if !self.expect_peek(Token::LBrace) {
+            return None;
+        }
+
+        let body = self.parse_loop_body();
+
+        return Some(ForStatement {
+            token,
+            variable,
+            iterable,
+            body,
+            span: start.to(self.cur_span),
+        });
     }
 
-    fn next_token(&mut self) {
-        // peek_token goes into cur_token and peek_token gets a new value
-        self.cur_token = std::mem::replace(&mut self.peek_token, self.lexer.next_token());
+    /// cur_token has to be the opening brace, the semicolon after the closing one is optional
+    fn parse_loop_body(&mut self) -> BlockStatement {
+        self.loop_depth += 1;
+        let body = self.parse_block_statement();
+        self.loop_depth -= 1;
+
+        if self.peek_token_is(&Token::Semicolon) {
+            self.next_token();
+        }
+
+        return body;
     }
 
-    fn get_errors(&self) -> &Vec<String> {
-        return &self.errors;
+    /// `break` or `continue`, the loop they apply to has to be in the same function
+    fn parse_loop_control(&mut self) -> Statement {
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+
+        if self.loop_depth == 0 {
+            let diagnostic = Diagnostic::error(
+                OUTSIDE_LOOP,
+                format!("`{}` outside of a loop", token.litteral()),
+                start,
+            );
+            self.push_error(diagnostic);
+        }
+
+        // the semicolon is optional
+        if self.peek_token_is(&Token::Semicolon) {
+            self.next_token();
+        }
+
+        let span = start.to(self.cur_span);
+
+        if token == Token::Break {
+            return Statement::Break(BreakStatement { token, span });
+        }
+
+        return Statement::Continue(ContinueStatement { token, span });
     }
 
-    fn peek_error(&mut self, expected_tok: Token) {
-        if self.peek_token_is(&expected_tok) {
+    /// `break` and `continue` can not leave the loop while a value is being computed, so only
+    /// the `if`s used as statements can contain them (ex: not the one of `1 + if (c) { break }`)
+    fn check_loop_control(&mut self, expression: &Expression) {
+        // otherwise they are reported as outside of a loop
+        if self.loop_depth == 0 {
             return;
         }
 
-        let msg = format!(
-            "Expected next token to be {}, got {} instead",
-            &expected_tok.to_string(),
-            self.peek_token
-        );
-        self.errors.push(msg);
+        let mut controls = Vec::new();
+        expression_loop_controls(expression, &mut controls);
+
+        for (token, span) in controls {
+            let diagnostic = Diagnostic::error(
+                LOOP_CONTROL_IN_EXPRESSION,
+                format!("`{}` inside an expression", token.litteral()),
+                span,
+            )
+            .with_help("use the `if` as a statement, its value can not be used");
+            self.push_error(diagnostic);
+        }
     }
 
-    // Pratt Parser helpers
+    fn parse_expression_statement(&mut self) -> Option<ExpressionStatement> {
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+        let expression = self.parse_expression(Precedence::Lowest)?;
+
+        // the blocks of an `if` used as a statement can leave the loop
+        match &expression {
+            Expression::If(if_expression) => self.check_loop_control(&if_expression.condition),
+            expression => self.check_loop_control(expression),
+        }
+
+        // the semicolon is optional so that `5 + 5` is valid in the REPL
+        if self.peek_token_is(&Token::Semicolon) {
+            self.next_token();
+        }
 
-    fn register_prefix(&mut self, token: Token, prefix_parse_fn: &'a PrefixParseFn) {
-        self.prefix_parse_fns.insert(token, prefix_parse_fn);
+        return Some(ExpressionStatement {
+            token,
+            expression,
+            span: start.to(self.cur_span),
+        });
     }
 
-    fn register_infix(&mut self, token: Token, infix_parse_fn: &'a InfixParseFn) {
-        self.infix_parse_fns.insert(token, infix_parse_fn);
+    // ===========================================
+    // Expressions
+
+    fn parse_expression(&mut self, precedence: Precedence) -> Option<Expression> {
+        if self.expression_depth >= MAX_NESTING {
+            let diagnostic = Diagnostic::error(
+                NESTED_TOO_DEEPLY,
+                "Expression nested too deeply".to_string(),
+                self.cur_span,
+            )
+            .with_note(&format!(
+                "expressions can be nested up to {} levels",
+                MAX_NESTING
+            ));
+            self.push_error(diagnostic);
+            return None;
+        }
+
+        self.expression_depth += 1;
+        let expression = self.parse_operators(precedence);
+        self.expression_depth -= 1;
+
+        return expression;
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::ast::{LetStatement, Node, ReturnStatement};
+    /// Pratt parsing: the prefix expression, then the infix operators binding tighter than
+    /// `precedence`
+    fn parse_operators(&mut self, precedence: Precedence) -> Option<Expression> {
+        let prefix = match self.prefix_parse_fns.get(&discriminant(&self.cur_token)) {
+            Some(prefix) => *prefix,
+            None => {
+                self.no_prefix_parse_fn_error();
+                return None;
+            }
+        };
 
-    use super::{Lexer, Parser, Statement};
-    use std::any::Any;
+        let mut left = prefix(self)?;
 
-    #[test]
-    fn test_let_statements() {
-        let test_str = "
-let x = 5;
-let y = 10;
-let foobar = 838383;
-";
+        while !self.peek_token_is(&Token::Semicolon) && precedence < self.peek_precedence() {
+            let peek = discriminant(&self.peek().token);
+            let infix = match self.infix_parse_fns.get(&peek) {
+                Some(infix) => *infix,
+                None => return Some(left),
+            };
 
-        let mut lexer = Lexer::new(test_str);
-        let mut parser = Parser::new(&mut lexer);
+            self.next_token();
+            left = infix(self, left)?;
+        }
 
-        let program = parser.parse_program();
-        check_parser_errors(&parser);
+        return Some(left);
+    }
 
-        let num_statements = program.statements.len();
-        assert_eq!(
-            num_statements, 3,
-            "Number of statements is wrong (expected: 3, actual: {})",
-            num_statements
-        );
+    fn parse_identifier(&mut self) -> Option<Expression> {
+        return Some(Expression::Identifier(self.cur_identifier()?));
+    }
 
-        let expected_statements = ["x", "y", "foobar"];
-        for (i, expected_statement) in expected_statements.iter().enumerate() {
-            test_let_statement(expected_statement.to_string(), &program.statements[i]);
+    fn parse_integer_literal(&mut self) -> Option<Expression> {
+        let value = match &self.cur_token {
+            Token::Int(Some(value), _) => *value,
+            Token::Int(None, lexeme) => {
+                let diagnostic = Diagnostic::error(
+                    INVALID_NUMBER,
+                    format!("Integer literal {} is out of range", lexeme),
+                    self.cur_span,
+                )
+                .with_note("integers range from -9223372036854775808 to 9223372036854775807");
+                self.push_error(diagnostic);
+                return None;
+            }
+            _ => return None,
+        };
+
+        return Some(Expression::Integer(IntegerLiteral {
+            token: self.cur_token.clone(),
+            value,
+            span: self.cur_span,
+        }));
+    }
+
+    fn parse_float_literal(&mut self) -> Option<Expression> {
+        let value = match &self.cur_token {
+            Token::Float(lexeme) => match lexeme.replace('_', "").parse::<f64>() {
+                Ok(value) if value.is_finite() => value,
+                _ => {
+                    let diagnostic = Diagnostic::error(
+                        INVALID_NUMBER,
+                        format!("Float literal {} is out of range", lexeme),
+                        self.cur_span,
+                    )
+                    .with_note("floats range from about -1.8e308 to 1.8e308");
+                    self.push_error(diagnostic);
+                    return None;
+                }
+            },
+            _ => return None,
+        };
+
+        return Some(Expression::Float(FloatLiteral {
+            token: self.cur_token.clone(),
+            value,
+            span: self.cur_span,
+        }));
+    }
+
+    fn parse_string_literal(&mut self) -> Option<Expression> {
+        let value = match &self.cur_token {
+            Token::String(value) => value.clone(),
+            _ => return None,
+        };
+
+        return Some(Expression::String(StringLiteral {
+            token: self.cur_token.clone(),
+            value,
+            span: self.cur_span,
+        }));
+    }
+
+    fn parse_boolean(&mut self) -> Option<Expression> {
+        return Some(Expression::Boolean(Boolean {
+            token: self.cur_token.clone(),
+            value: self.cur_token_is(&Token::True),
+            span: self.cur_span,
+        }));
+    }
+
+    fn parse_prefix_expression(&mut self) -> Option<Expression> {
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+        let operator = token.litteral();
+
+        self.next_token();
+
+        // the smallest integer can only be written negated, its literal alone is out of range
+        if let Token::Int(None, lexeme) = &self.cur_token
+            && token == Token::Minus
+            && integer_magnitude(lexeme) == Some(i64::MIN.unsigned_abs())
+        {
+            return Some(Expression::Integer(IntegerLiteral {
+                token: Token::Int(Some(i64::MIN), format!("-{}", lexeme)),
+                value: i64::MIN,
+                span: start.to(self.cur_span),
+            }));
         }
+
+        let right = self.parse_expression(Precedence::Prefix)?;
+
+        return Some(Expression::Prefix(PrefixExpression {
+            token,
+            operator,
+            span: start.to(right.span()),
+            right: Box::new(right),
+        }));
     }
 
-    #[test]
-    fn test_return_statements() {
-        let test_str = "
-return 5;
-return 10;
-return 993322;
-";
+    fn parse_infix_expression(&mut self, left: Expression) -> Option<Expression> {
+        let token = self.cur_token.clone();
+        let operator = token.litteral();
+        let precedence = self.cur_precedence();
 
-        let mut lexer = Lexer::new(test_str);
-        let mut parser = Parser::new(&mut lexer);
+        self.next_token();
 
-        let program = parser.parse_program();
-        check_parser_errors(&parser);
+        let right = self.parse_expression(precedence)?;
 
-        let num_statements = program.statements.len();
-        assert_eq!(
-            num_statements, 3,
-            "Number of statements is wrong (expected: 3, actual: {})",
-            num_statements
-        );
+        return Some(Expression::Infix(InfixExpression {
+            token,
+            span: left.span().to(right.span()),
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+        }));
+    }
 
-        for statement in program.statements {
-            let return_statement: &ReturnStatement = if let Some(return_s) =
-                (&*statement as &dyn Any).downcast_ref::<ReturnStatement>()
-            {
-                return_s
-            } else {
-                panic!("Could not extract ReturnStatement from Statement.")
-            };
+    fn parse_grouped_expression(&mut self) -> Option<Expression> {
+        let open = self.cur_span;
+        self.next_token();
 
-            assert_eq!(
-                return_statement.token_litteral(),
-                "return",
-                "Token litteral is not 'return' but is {}",
-                return_statement.token_litteral()
-            );
+        let expression = self.parse_expression(Precedence::Lowest)?;
+
+        if !self.expect_closing(Token::RParen, open) {
+            return None;
         }
+
+        return Some(expression);
     }
 
-    // ===========================================
-    // HELPERS
-    fn test_let_statement(expected: String, actual: &Box<dyn Statement>) {
-        assert_eq!(actual.token_litteral(), "let".to_string());
+    fn parse_if_expression(&mut self) -> Option<Expression> {
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
 
-        let let_statement =
-            if let Some(let_s) = (&**actual as &dyn Any).downcast_ref::<LetStatement>() {
-                let_s
-            } else {
-                panic!("Could not extract LetStatement from Statement.")
-            };
+        if !self.expect_peek(Token::LParen) {
+            return None;
+        }
+        let open = self.cur_span;
 
-        assert_eq!(
-            let_statement.identifier.value, expected,
-            "Value of identifier is wrong (expected: {}, actual: {})",
-            let_statement.identifier.value, expected
-        );
+        self.next_token();
+        let condition = self.parse_expression(Precedence::Lowest)?;
 
-        assert_eq!(
-            let_statement.identifier.token_litteral(),
-            expected,
-            "Value returned from token_litteral is wrong (expected: {}, actual: {})",
-            let_statement.identifier.token_litteral(),
-            expected
-        );
+        if !self.expect_closing(Token::RParen, open) {
+            return None;
+        }
+
+        if !self.expect_peek(Token::LBrace) {
+            return None;
+        }
+
+        let consequence = self.parse_block_statement();
+
+        let mut alternative = None;
+        if self.peek_token_is(&Token::Else) {
+            self.next_token();
+
+            if !self.expect_peek(Token::LBrace) {
+                return None;
+            }
+
+            alternative = Some(self.parse_block_statement());
+        }
+
+        return Some(Expression::If(IfExpression {
+            token,
+            condition: Box::new(condition),
+            consequence,
+            alternative,
+            span: start.to(self.cur_span),
+        }));
+    }
+
+    /// parses the statements up to the closing brace (cur_token has to be the opening brace)
+    fn parse_block_statement(&mut self) -> BlockStatement {
+        let mut block = BlockStatement {
+            token: self.cur_token.clone(),
+            statements: Vec::new(),
+            span: self.cur_span,
+        };
+
+        self.next_token();
+        self.block_depth += 1;
+
+        while !self.cur_token_is(&Token::RBrace) && !self.cur_token_is(&Token::Eof) {
+            let statement = self.parse_statement_or_recover();
+            let recovered = matches!(statement, Statement::Error(_));
+            block.statements.push(statement);
+
+            // the recovery stopped on the closing brace (ex: `{ 1 + }`)
+            if recovered && self.cur_token_is(&Token::RBrace) {
+                continue;
+            }
+
+            self.next_token();
+        }
+
+        self.block_depth -= 1;
+
+        if self.cur_token_is(&Token::Eof) {
+            let diagnostic = Diagnostic::error(
+                UNEXPECTED_TOKEN,
+                "Expected next token to be RBrace, got Eof instead".to_string(),
+                self.cur_span,
+            )
+            .with_label(block.span, "`{` opened here");
+            self.push_error(diagnostic);
+        }
+
+        block.span = block.span.to(self.cur_span);
+
+        return block;
+    }
+
+    fn parse_function_literal(&mut self) -> Option<Expression> {
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+
+        if !self.expect_peek(Token::LParen) {
+            return None;
+        }
+
+        let parameters = self.parse_function_parameters()?;
+
+        if !self.expect_peek(Token::LBrace) {
+            return None;
+        }
+
+        // the loops around the function can not be controlled from its body
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        let body = self.parse_block_statement();
+        self.loop_depth = loop_depth;
+
+        return Some(Expression::Function(FunctionLiteral {
+            token,
+            parameters,
+            body,
+            span: start.to(self.cur_span),
+        }));
+    }
+
+    fn parse_function_parameters(&mut self) -> Option<Vec<Identifier>> {
+        let open = self.cur_span;
+        let mut identifiers = Vec::new();
+
+        if self.peek_token_is(&Token::RParen) {
+            self.next_token();
+            return Some(identifiers);
+        }
+
+        if !self.expect_peek(Token::Ident(String::new())) {
+            return None;
+        }
+        identifiers.push(self.cur_identifier()?);
+
+        while self.peek_token_is(&Token::Comma) {
+            self.next_token();
+
+            if !self.expect_peek(Token::Ident(String::new())) {
+                return None;
+            }
+            identifiers.push(self.cur_identifier()?);
+        }
+
+        if !self.expect_closing(Token::RParen, open) {
+            return None;
+        }
+
+        return Some(identifiers);
+    }
+
+    fn parse_call_expression(&mut self, function: Expression) -> Option<Expression> {
+        let token = self.cur_token.clone();
+        let arguments = self.parse_expression_list(Token::RParen)?;
+
+        return Some(Expression::Call(CallExpression {
+            token,
+            span: function.span().to(self.cur_span),
+            function: Box::new(function),
+            arguments,
+        }));
+    }
+
+    fn parse_array_literal(&mut self) -> Option<Expression> {
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+
+        let elements = self.parse_expression_list(Token::RBracket)?;
+
+        return Some(Expression::Array(ArrayLiteral {
+            token,
+            elements,
+            span: start.to(self.cur_span),
+        }));
+    }
+
+    /// NOTE: blocks only come after `if`, `else` and `fn(...)`, a `{` anywhere else opens a hash
+    fn parse_hash_literal(&mut self) -> Option<Expression> {
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+
+        let mut pairs = Vec::new();
+
+        while !self.peek_token_is(&Token::RBrace) {
+            self.next_token();
+            let key = self.parse_expression(Precedence::Lowest)?;
+
+            if !self.expect_peek(Token::Colon) {
+                return None;
+            }
+
+            self.next_token();
+            let value = self.parse_expression(Precedence::Lowest)?;
+
+            pairs.push((key, value));
+
+            if !self.peek_token_is(&Token::RBrace) && !self.expect_peek(Token::Comma) {
+                return None;
+            }
+        }
+
+        if !self.expect_closing(Token::RBrace, start) {
+            return None;
+        }
+
+        return Some(Expression::Hash(HashLiteral {
+            token,
+            pairs,
+            span: start.to(self.cur_span),
+        }));
+    }
+
+    fn parse_index_expression(&mut self, left: Expression) -> Option<Expression> {
+        let token = self.cur_token.clone();
+        let open = self.cur_span;
+
+        self.next_token();
+        let index = self.parse_expression(Precedence::Lowest)?;
+
+        if !self.expect_closing(Token::RBracket, open) {
+            return None;
+        }
+
+        return Some(Expression::Index(IndexExpression {
+            token,
+            span: left.span().to(self.cur_span),
+            left: Box::new(left),
+            index: Box::new(index),
+        }));
+    }
+
+    /// parses comma separated expressions up to the `end` token (ex: call arguments)
+    fn parse_expression_list(&mut self, end: Token) -> Option<Vec<Expression>> {
+        let open = self.cur_span;
+        let mut list = Vec::new();
+
+        if self.peek_token_is(&end) {
+            self.next_token();
+            return Some(list);
+        }
+
+        self.next_token();
+        list.push(self.parse_expression(Precedence::Lowest)?);
+
+        while self.peek_token_is(&Token::Comma) {
+            self.next_token();
+            self.next_token();
+            list.push(self.parse_expression(Precedence::Lowest)?);
+        }
+
+        if !self.expect_closing(end, open) {
+            return None;
+        }
+
+        return Some(list);
+    }
+
+    // ===========================================
+    // Token helpers
+
+    /// builds an identifier out of cur_token (has to be a Token::Ident)
+    fn cur_identifier(&self) -> Option<Identifier> {
+        return match &self.cur_token {
+            Token::Ident(ident_name) => Some(Identifier {
+                token: self.cur_token.clone(),
+                value: ident_name.to_string(),
+                span: self.cur_span,
+            }),
+            _ => None,
+        };
+    }
+
+    fn cur_token_is(&self, expected_tok: &Token) -> bool {
+        return std::mem::discriminant(&self.cur_token) == std::mem::discriminant(expected_tok);
+    }
+
+    fn peek_token_is(&mut self, expected_tok: &Token) -> bool {
+        return std::mem::discriminant(&self.peek().token) == std::mem::discriminant(expected_tok);
+    }
+
+    fn expect_peek(&mut self, expected_tok: Token) -> bool {
+        if self.peek_token_is(&expected_tok) {
+            self.next_token();
+            return true;
+        } else {
+            let diagnostic = self.peek_error(expected_tok);
+            self.push_error(diagnostic);
+            return false;
+        }
+    }
+
+    /// expect_peek for the token closing the delimiter opened at `open`
+    fn expect_closing(&mut self, expected_tok: Token, open: Span) -> bool {
+        if self.peek_token_is(&expected_tok) {
+            self.next_token();
+            return true;
+        }
+
+        let opening = match expected_tok {
+            Token::RParen => "(",
+            Token::RBrace => "{",
+            _ => "[",
+        };
+
+        let diagnostic = self
+            .peek_error(expected_tok)
+            .with_label(open, &format!("`{}` opened here", opening));
+        self.push_error(diagnostic);
+        return false;
+    }
+
+    fn next_token(&mut self) {
+        let next = self.lexer.next_token();
+        self.cur_token = next.token;
+        self.cur_span = next.span;
+        self.cur_doc = next.doc;
+    }
+
+    /// token right after cur_token (`self.lexer.peek_nth(k)` looks further ahead)
+    fn peek(&mut self) -> &SpannedToken {
+        return self.lexer.peek_nth(0);
+    }
+
+    pub fn get_errors(&self) -> &Vec<Diagnostic> {
+        return &self.errors;
+    }
+
+    /// Records the error unless it is reported at the same place as the previous one
+    /// or there are already too many of them
+    fn push_error(&mut self, diagnostic: Diagnostic) {
+        if self.errors.len() >= MAX_ERRORS {
+            return;
+        }
+
+        if let Some(previous) = self.errors.last()
+            && previous.span.start == diagnostic.span.start
+        {
+            return;
+        }
+
+        self.errors.push(diagnostic);
+    }
+
+    fn peek_error(&mut self, expected_tok: Token) -> Diagnostic {
+        let peek = self.peek().clone();
+
+        if let Some(diagnostic) = lexer_error(&peek.token, peek.span) {
+            return diagnostic;
+        }
+
+        return Diagnostic::error(
+            UNEXPECTED_TOKEN,
+            format!(
+                "Expected next token to be {}, got {} instead",
+                &expected_tok.to_string(),
+                peek.token
+            ),
+            peek.span,
+        );
+    }
+
+    fn no_prefix_parse_fn_error(&mut self) {
+        if let Some(diagnostic) = lexer_error(&self.cur_token, self.cur_span) {
+            self.push_error(diagnostic);
+            return;
+        }
+
+        let mut diagnostic = Diagnostic::error(
+            EXPECTED_EXPRESSION,
+            format!("No prefix parse function for {} found", self.cur_token),
+            self.cur_span,
+        );
+        if self.cur_token == Token::Eof {
+            diagnostic = diagnostic.with_help("the input ends before the expression is complete");
+        }
+
+        self.push_error(diagnostic);
+    }
+
+    // Pratt Parser helpers
+
+    fn peek_precedence(&mut self) -> Precedence {
+        return Precedence::of(&self.peek().token);
+    }
+
+    fn cur_precedence(&self) -> Precedence {
+        return Precedence::of(&self.cur_token);
+    }
+
+    fn register_prefix(&mut self, token: Token, prefix_parse_fn: PrefixParseFn<'a>) {
+        self.prefix_parse_fns
+            .insert(discriminant(&token), prefix_parse_fn);
+    }
+
+    fn register_infix(&mut self, token: Token, infix_parse_fn: InfixParseFn<'a>) {
+        self.infix_parse_fns
+            .insert(discriminant(&token), infix_parse_fn);
+    }
+}
+
+/// `break`s and `continue`s of the `if` blocks in the expression (without the ones of nested
+/// loops and functions)
+fn expression_loop_controls(expression: &Expression, controls: &mut Vec<(Token, Span)>) {
+    match expression {
+        Expression::Prefix(prefix) => expression_loop_controls(&prefix.right, controls),
+        Expression::Infix(infix) => {
+            expression_loop_controls(&infix.left, controls);
+            expression_loop_controls(&infix.right, controls);
+        }
+        Expression::If(if_expression) => {
+            expression_loop_controls(&if_expression.condition, controls);
+            block_loop_controls(&if_expression.consequence, controls);
+            if let Some(alternative) = &if_expression.alternative {
+                block_loop_controls(alternative, controls);
+            }
+        }
+        Expression::Call(call) => {
+            expression_loop_controls(&call.function, controls);
+            for argument in &call.arguments {
+                expression_loop_controls(argument, controls);
+            }
+        }
+        Expression::Array(array) => {
+            for element in &array.elements {
+                expression_loop_controls(element, controls);
+            }
+        }
+        Expression::Index(index) => {
+            expression_loop_controls(&index.left, controls);
+            expression_loop_controls(&index.index, controls);
+        }
+        Expression::Hash(hash) => {
+            for (key, value) in &hash.pairs {
+                expression_loop_controls(key, controls);
+                expression_loop_controls(value, controls);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// NOTE: the values of the other statements of the block are checked when they are parsed
+fn block_loop_controls(block: &BlockStatement, controls: &mut Vec<(Token, Span)>) {
+    for statement in &block.statements {
+        match statement {
+            Statement::Break(statement) => controls.push((statement.token.clone(), statement.span)),
+            Statement::Continue(statement) => {
+                controls.push((statement.token.clone(), statement.span));
+            }
+            Statement::Expression(statement) => {
+                expression_loop_controls(&statement.expression, controls);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn is_assignment(token: &Token) -> bool {
+    return matches!(
+        token,
+        Token::Assign
+            | Token::PlusAssign
+            | Token::MinusAssign
+            | Token::AsteriskAssign
+            | Token::SlashAssign
+    );
+}
+
+/// Diagnostic for the tokens the lexer produces when it cannot make sense of the input
+pub(crate) fn lexer_error(token: &Token, span: Span) -> Option<Diagnostic> {
+    return match token {
+        Token::Illegal(character) => Some(Diagnostic::error(
+            ILLEGAL_CHARACTER,
+            format!("Illegal character `{}`", character),
+            span,
+        )),
+        Token::InvalidString(reason) => {
+            let diagnostic =
+                Diagnostic::error(INVALID_STRING, format!("Invalid string: {}", reason), span);
+
+            if reason.contains("escape") {
+                return Some(
+                    diagnostic.with_help(
+                        "the escape sequences are \\n \\t \\r \\0 \\\" \\\\ and \\u{...}",
+                    ),
+                );
+            }
+
+            Some(diagnostic)
+        }
+        Token::InvalidNumber(reason) => Some(
+            Diagnostic::error(INVALID_NUMBER, format!("Invalid number: {}", reason), span)
+                .with_help("numbers are written as 42, 1_000, 0x2a, 0o52, 0b101010, 3.14 or 1e-9"),
+        ),
+        Token::UnterminatedString => Some(Diagnostic::error(
+            INVALID_STRING,
+            "Invalid string: unterminated string".to_string(),
+            span,
+        )),
+        Token::UnterminatedComment => Some(
+            Diagnostic::error(
+                UNTERMINATED_COMMENT,
+                "Unterminated block comment".to_string(),
+                span,
+            )
+            .with_note("block comments nest, each `/*` needs its own `*/`"),
+        ),
+        _ => None,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{
+        BlockStatement, Boolean, CallExpression, Expression, ExpressionStatement, FunctionLiteral,
+        Identifier, IfExpression, InfixExpression, IntegerLiteral, LetStatement, Node,
+        PrefixExpression, Program, StringLiteral,
+    };
+
+    use super::{
+        EXPECTED_EXPRESSION, INVALID_NUMBER, INVALID_STRING, Lexer, NESTED_TOO_DEEPLY, Parser,
+        Span, Statement, Token, UNEXPECTED_TOKEN, UNTERMINATED_COMMENT,
+    };
+    use crate::diagnostic::Label;
+
+    #[test]
+    fn test_let_statements() {
+        let test_str = "
+let x = 5;
+let y = 10;
+let foobar = 838383;
+";
+
+        let mut lexer = Lexer::new(test_str);
+        let mut parser = Parser::new(&mut lexer);
+
+        let program = parser.parse_program();
+        check_parser_errors(&parser);
+
+        let num_statements = program.statements.len();
+        assert_eq!(
+            num_statements, 3,
+            "Number of statements is wrong (expected: 3, actual: {})",
+            num_statements
+        );
+
+        let expected_statements = [("x", 5), ("y", 10), ("foobar", 838383)];
+        for (i, (expected_statement, expected_value)) in expected_statements.iter().enumerate() {
+            test_let_statement(expected_statement.to_string(), &program.statements[i]);
+
+            let Statement::Let(let_statement) = &program.statements[i] else {
+                panic!("Could not extract LetStatement from Statement.")
+            };
+            test_integer_literal(&let_statement.value, *expected_value);
+        }
+    }
+
+    #[test]
+    fn test_let_and_return_values() {
+        let tests = [
+            ("let x = 5;", "let x = 5;"),
+            ("let y = true", "let y = true;"),
+            ("let foobar = y + -z * 2;", "let foobar = (y + ((-z) * 2));"),
+            ("return 5", "return 5;"),
+            ("return x == 10;", "return (x == 10);"),
+            (
+                "let a = 1 let b = a return b",
+                "let a = 1;\nlet b = a;\nreturn b;",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input);
+            assert_eq!(program.to_string(), expected, "Wrong parse for `{input}`");
+        }
+    }
+
+    #[test]
+    fn test_let_statement_missing_value() {
+        let mut lexer = Lexer::new("let x =");
+        let mut parser = Parser::new(&mut lexer);
+
+        let program = parser.parse_program();
+
+        assert_eq!(program.statements.len(), 1);
+        assert!(matches!(program.statements[0], Statement::Error(_)));
+        assert_eq!(
+            parser
+                .get_errors()
+                .iter()
+                .map(|error| error.to_string())
+                .collect::<Vec<String>>(),
+            vec!["line 1, column 8: No prefix parse function for Eof found".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_assign_statements() {
+        let tests = [
+            ("x = x + 1;", "x", None, "x = (x + 1);"),
+            ("x += 1;", "x", Some("+"), "x += 1;"),
+            ("total -= a * b", "total", Some("-"), "total -= (a * b);"),
+            ("x *= 2; x", "x", Some("*"), "x *= 2;\nx"),
+            ("y /= f(2);", "y", Some("/"), "y /= f(2);"),
+        ];
+
+        for (input, name, operator, printed) in tests {
+            let program = parse(input);
+            assert_eq!(program.to_string(), printed, "Wrong parse for `{input}`");
+
+            let Statement::Assign(assign) = &program.statements[0] else {
+                panic!("Expected an AssignStatement for `{input}`");
+            };
+            assert_eq!(assign.identifier.value, name);
+            assert_eq!(assign.operator(), operator);
+        }
+
+        // the target has to be a name
+        let mut lexer = Lexer::new("1 += 2;");
+        let mut parser = Parser::new(&mut lexer);
+        parser.parse_program();
+        assert_eq!(
+            parser.get_errors()[0].message,
+            "No prefix parse function for PlusAssign found"
+        );
+    }
+
+    #[test]
+    fn test_loop_statements() {
+        let tests = [
+            ("while (x < 10) { x += 1; }", "while (x < 10) { x += 1; }"),
+            ("while (true) { break; };", "while (true) { break; }"),
+            (
+                "for (x in [1, 2]) { if (x == 1) { continue } total += x }",
+                "for (x in [1, 2]) { if (x == 1) { continue; }; total += x; }",
+            ),
+            (
+                "for (row in rows) { for (cell in row) { break; } }",
+                "for (row in rows) { for (cell in row) { break; } }",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input);
+            assert_eq!(
+                program.statements.len(),
+                1,
+                "Wrong statements for `{input}`"
+            );
+            assert_eq!(program.to_string(), expected, "Wrong parse for `{input}`");
+        }
+
+        let program = parse("for (item in items) { item }");
+        let Statement::For(for_statement) = &program.statements[0] else {
+            panic!("Expected a ForStatement");
+        };
+        assert_eq!(for_statement.variable.value, "item");
+        test_identifier(&for_statement.iterable, "items");
+        assert_eq!(for_statement.body.statements.len(), 1);
+    }
+
+    #[test]
+    fn test_loop_control_outside_loop() {
+        let tests = [
+            (
+                "break;",
+                vec!["line 1, column 1: `break` outside of a loop"],
+            ),
+            (
+                "if (true) { continue }",
+                vec!["line 1, column 13: `continue` outside of a loop"],
+            ),
+            // a function does not see the loops around it
+            (
+                "while (true) { let f = fn() { break; }; break; }",
+                vec!["line 1, column 31: `break` outside of a loop"],
+            ),
+            ("while (true) { if (x) { break; } continue; }", vec![]),
+            (
+                "while (true) { if (x) { if (y) { break } else { continue } } }",
+                vec![],
+            ),
+            // the value of the `if` is being computed
+            (
+                "while (true) { let y = if (x) { break }; }",
+                vec!["line 1, column 33: `break` inside an expression"],
+            ),
+            (
+                "for (x in xs) { 1 + if (x) { continue } }",
+                vec!["line 1, column 30: `continue` inside an expression"],
+            ),
+            (
+                "while (true) { [1, if (x) { if (y) { continue } }] }",
+                vec!["line 1, column 38: `continue` inside an expression"],
+            ),
+            (
+                "while (true) { f(if (x) { 1 } else { break }); }",
+                vec!["line 1, column 38: `break` inside an expression"],
+            ),
+            (
+                "while (true) { while (if (x) { break }) { } }",
+                vec!["line 1, column 32: `break` inside an expression"],
+            ),
+            (
+                "while (true) { if (x) { break } + 1 }",
+                vec!["line 1, column 25: `break` inside an expression"],
+            ),
+            (
+                "while (true) { if (if (x) { break }) { } }",
+                vec!["line 1, column 29: `break` inside an expression"],
+            ),
+            (
+                "while (true) { let f = fn() { while (true) { return if (x) { break } } }; }",
+                vec!["line 1, column 62: `break` inside an expression"],
+            ),
+            (
+                "for (x 1) { }",
+                vec!["line 1, column 8: Expected next token to be In, got Int(1) instead"],
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(input);
+            let mut parser = Parser::new(&mut lexer);
+            parser.parse_program();
+
+            let errors: Vec<String> = parser.get_errors().iter().map(|e| e.to_string()).collect();
+            assert_eq!(errors, expected, "Wrong errors for `{input}`");
+        }
+    }
+
+    #[test]
+    fn test_return_statements() {
+        let test_str = "
+return 5;
+return 10;
+return 993322;
+";
+
+        let mut lexer = Lexer::new(test_str);
+        let mut parser = Parser::new(&mut lexer);
+
+        let program = parser.parse_program();
+        check_parser_errors(&parser);
+
+        let num_statements = program.statements.len();
+        assert_eq!(
+            num_statements, 3,
+            "Number of statements is wrong (expected: 3, actual: {})",
+            num_statements
+        );
+
+        let expected_values = [5, 10, 993322];
+        for (statement, expected_value) in program.statements.iter().zip(expected_values) {
+            let return_statement = if let Statement::Return(return_s) = statement {
+                return_s
+            } else {
+                panic!("Could not extract ReturnStatement from Statement.")
+            };
+
+            assert_eq!(
+                return_statement.token_litteral(),
+                "return",
+                "Token litteral is not 'return' but is {}",
+                return_statement.token_litteral()
+            );
+            test_integer_literal(&return_statement.return_value, expected_value);
+        }
+    }
+
+    #[test]
+    fn test_identifier_expression() {
+        let program = parse("foobar;");
+
+        assert_eq!(program.statements.len(), 1);
+        let expression = expression_of(&program.statements[0]);
+        test_identifier(expression, "foobar");
+    }
+
+    #[test]
+    fn test_integer_literal_expression() {
+        let program = parse("5;");
+
+        assert_eq!(program.statements.len(), 1);
+        let expression = expression_of(&program.statements[0]);
+        test_integer_literal(expression, 5);
+    }
+
+    #[test]
+    fn test_boolean_expression() {
+        let tests = [("true;", true), ("false;", false)];
+
+        for (input, expected) in tests {
+            let program = parse(input);
+
+            assert_eq!(program.statements.len(), 1);
+            let expression = expression_of(&program.statements[0]);
+            test_boolean(expression, expected);
+        }
+    }
+
+    #[test]
+    fn test_prefix_expressions() {
+        let tests = [("!5;", "!", 5), ("-15;", "-", 15)];
+
+        for (input, operator, value) in tests {
+            let program = parse(input);
+
+            assert_eq!(program.statements.len(), 1);
+            let expression = expression_of(&program.statements[0]);
+
+            let prefix = if let Expression::Prefix(prefix) = expression {
+                prefix
+            } else {
+                panic!("Could not extract PrefixExpression from Expression.")
+            };
+
+            assert_eq!(prefix.operator, operator);
+            test_integer_literal(&prefix.right, value);
+        }
+    }
+
+    #[test]
+    fn test_infix_expressions() {
+        let tests = [
+            ("5 + 5;", 5, "+", 5),
+            ("5 - 5;", 5, "-", 5),
+            ("5 * 5;", 5, "*", 5),
+            ("5 / 5;", 5, "/", 5),
+            ("5 > 5;", 5, ">", 5),
+            ("5 < 5;", 5, "<", 5),
+            ("5 == 5;", 5, "==", 5),
+            ("5 != 5;", 5, "!=", 5),
+        ];
+
+        for (input, left, operator, right) in tests {
+            let program = parse(input);
+
+            assert_eq!(program.statements.len(), 1);
+            let expression = expression_of(&program.statements[0]);
+
+            let infix = if let Expression::Infix(infix) = expression {
+                infix
+            } else {
+                panic!("Could not extract InfixExpression from Expression.")
+            };
+
+            test_integer_literal(&infix.left, left);
+            assert_eq!(infix.operator, operator);
+            test_integer_literal(&infix.right, right);
+        }
+    }
+
+    #[test]
+    fn test_operator_precedence_parsing() {
+        let tests = [
+            ("-a * b", "((-a) * b)"),
+            ("!-a", "(!(-a))"),
+            ("a + b + c", "((a + b) + c)"),
+            ("a + b - c", "((a + b) - c)"),
+            ("a * b * c", "((a * b) * c)"),
+            ("a * b / c", "((a * b) / c)"),
+            ("a + b / c", "(a + (b / c))"),
+            ("a + b * c + d / e - f", "(((a + (b * c)) + (d / e)) - f)"),
+            ("3 + 4; -5 * 5", "(3 + 4);\n((-5) * 5)"),
+            ("5 > 4 == 3 < 4", "((5 > 4) == (3 < 4))"),
+            ("5 < 4 != 3 > 4", "((5 < 4) != (3 > 4))"),
+            (
+                "3 + 4 * 5 == 3 * 1 + 4 * 5",
+                "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))",
+            ),
+            ("true", "true"),
+            ("3 > 5 == false", "((3 > 5) == false)"),
+            ("3 < 5 == true", "((3 < 5) == true)"),
+            ("1 + (2 + 3) + 4", "((1 + (2 + 3)) + 4)"),
+            ("(5 + 5) * 2", "((5 + 5) * 2)"),
+            ("2 / (5 + 5)", "(2 / (5 + 5))"),
+            ("-(5 + 5)", "(-(5 + 5))"),
+            ("!(true == true)", "(!(true == true))"),
+            ("a + add(b * c) + d", "((a + add((b * c))) + d)"),
+            (
+                "add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8))",
+                "add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)))",
+            ),
+            (
+                "add(a + b + c * d / f + g)",
+                "add((((a + b) + ((c * d) / f)) + g))",
+            ),
+            (
+                "a * [1, 2, 3, 4][b * c] * d",
+                "((a * ([1, 2, 3, 4][(b * c)])) * d)",
+            ),
+            (
+                "add(a * b[2], b[1], 2 * [1, 2][1])",
+                "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))",
+            ),
+            ("a + b % c", "(a + (b % c))"),
+            ("a % b * c", "((a % b) * c)"),
+            ("a <= b == b >= a", "((a <= b) == (b >= a))"),
+            ("a || b && c", "(a || (b && c))"),
+            ("a && b || c", "((a && b) || c)"),
+            ("a == b && !c", "((a == b) && (!c))"),
+            ("x < 1 || y >= 2", "((x < 1) || (y >= 2))"),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input);
+            assert_eq!(program.to_string(), expected, "Wrong parse for `{input}`");
+        }
+    }
+
+    #[test]
+    fn test_if_expression() {
+        let program = parse("if (x < y) { x }");
+
+        assert_eq!(program.statements.len(), 1);
+        let expression = expression_of(&program.statements[0]);
+
+        let if_expression = if let Expression::If(if_e) = expression {
+            if_e
+        } else {
+            panic!("Could not extract IfExpression from Expression.")
+        };
+
+        assert_eq!(if_expression.condition.to_string(), "(x < y)");
+        assert_eq!(if_expression.consequence.statements.len(), 1);
+        test_identifier(expression_of(&if_expression.consequence.statements[0]), "x");
+        assert!(if_expression.alternative.is_none());
+    }
+
+    #[test]
+    fn test_if_else_expression() {
+        let program = parse("if (x < y) { x } else { y }");
+
+        assert_eq!(program.statements.len(), 1);
+        let expression = expression_of(&program.statements[0]);
+
+        let if_expression = if let Expression::If(if_e) = expression {
+            if_e
+        } else {
+            panic!("Could not extract IfExpression from Expression.")
+        };
+
+        assert_eq!(if_expression.condition.to_string(), "(x < y)");
+        test_identifier(expression_of(&if_expression.consequence.statements[0]), "x");
+
+        let alternative = if_expression.alternative.as_ref().unwrap();
+        assert_eq!(alternative.statements.len(), 1);
+        test_identifier(expression_of(&alternative.statements[0]), "y");
+
+        assert_eq!(program.to_string(), "if (x < y) { x } else { y }");
+    }
+
+    #[test]
+    fn test_function_literal() {
+        let program = parse("fn(x, y) { x + y; }");
+
+        assert_eq!(program.statements.len(), 1);
+        let expression = expression_of(&program.statements[0]);
+
+        let function = if let Expression::Function(func) = expression {
+            func
+        } else {
+            panic!("Could not extract FunctionLiteral from Expression.")
+        };
+
+        assert_eq!(function.parameters.len(), 2);
+        assert_eq!(function.parameters[0].value, "x");
+        assert_eq!(function.parameters[1].value, "y");
+
+        assert_eq!(function.body.statements.len(), 1);
+        assert_eq!(function.body.statements[0].to_string(), "(x + y)");
+
+        assert_eq!(program.to_string(), "fn(x, y) { (x + y) }");
+    }
+
+    #[test]
+    fn test_function_parameters() {
+        let tests: [(&str, &[&str]); 3] = [
+            ("fn() {};", &[]),
+            ("fn(x) {};", &["x"]),
+            ("fn(x, y, z) {};", &["x", "y", "z"]),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input);
+            let expression = expression_of(&program.statements[0]);
+            let Expression::Function(function) = expression else {
+                panic!("Could not extract FunctionLiteral from Expression.")
+            };
+
+            let parameters: Vec<&str> = function
+                .parameters
+                .iter()
+                .map(|p| p.value.as_str())
+                .collect();
+            assert_eq!(parameters, expected);
+        }
+    }
+
+    #[test]
+    fn test_call_expression() {
+        let program = parse("add(1, 2 * 3, 4 + 5);");
+
+        assert_eq!(program.statements.len(), 1);
+        let expression = expression_of(&program.statements[0]);
+
+        let call = if let Expression::Call(call) = expression {
+            call
+        } else {
+            panic!("Could not extract CallExpression from Expression.")
+        };
+
+        test_identifier(&call.function, "add");
+        assert_eq!(call.arguments.len(), 3);
+        test_integer_literal(&call.arguments[0], 1);
+        assert_eq!(call.arguments[1].to_string(), "(2 * 3)");
+        assert_eq!(call.arguments[2].to_string(), "(4 + 5)");
+    }
+
+    #[test]
+    fn test_function_program() {
+        let program = parse(
+            "let add = fn(x, y) { return x + y; };
+let result = add(5, fn(a) { a * 2 }(3));
+if (result > 10) { true } else { false }",
+        );
+
+        assert_eq!(
+            program.to_string(),
+            "let add = fn(x, y) { return (x + y); };
+let result = add(5, fn(a) { (a * 2) }(3));
+if (result > 10) { true } else { false }"
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let tests = [
+            "let f = fn(x) { if (!x) { f(x - 1) } else { true } };",
+            "let a = 1 let b = a return b",
+            "3 + 4; -5 * 5",
+            "f; (1)",
+            "if (x) { {x: 1} } else { {} }",
+            "if (a) { 1 }; -1",
+            "fn() {}",
+            "add(1, fn(a) { a * 2; a }(3))[0]",
+            "/// the answer\nlet x = 42;",
+            r#"let s = "a\tb"; s + "c""#,
+            "x += 1; x",
+            "while (true) { x = x + 1; if (x > 3) { break } } x",
+            "for (x in [1, 2]) { continue; } for (y in f()) {}",
+        ];
+
+        // the printed program reads back as the same tree, so printing it again gives the same text
+        // (spans are not compared: the printed form adds parentheses and changes the layout)
+        for input in tests {
+            let printed = parse(input).to_string();
+            assert_eq!(
+                parse(&printed).to_string(),
+                printed,
+                "`{input}` does not round-trip"
+            );
+        }
+    }
+
+    #[test]
+    fn test_structural_comparison() {
+        let program = parse("let f = fn(x) { if (!x) { f(x - 1) } else { true } };");
+
+        // the whole program is on the first line
+        let span = |start, end| Span {
+            start,
+            end,
+            line: 1,
+            column: start + 1,
+        };
+        let x = |start| Identifier {
+            token: Token::Ident("x".to_string()),
+            value: "x".to_string(),
+            span: span(start, start + 1),
+        };
+        let f = |start| Identifier {
+            token: Token::Ident("f".to_string()),
+            value: "f".to_string(),
+            span: span(start, start + 1),
+        };
+
+        let condition = Expression::Prefix(PrefixExpression {
+            token: Token::Bang,
+            operator: "!".to_string(),
+            right: Box::new(Expression::Identifier(x(21))),
+            span: span(20, 22),
+        });
+        let recursive_call = Expression::Call(CallExpression {
+            token: Token::LParen,
+            function: Box::new(Expression::Identifier(f(26))),
+            arguments: vec![Expression::Infix(InfixExpression {
+                token: Token::Minus,
+                left: Box::new(Expression::Identifier(x(28))),
+                operator: "-".to_string(),
+                right: Box::new(Expression::Integer(IntegerLiteral {
+                    token: Token::Int(Some(1), "1".to_string()),
+                    value: 1,
+                    span: span(32, 33),
+                })),
+                span: span(28, 33),
+            })],
+            span: span(26, 34),
+        });
+        let if_expression = Expression::If(IfExpression {
+            token: Token::If,
+            condition: Box::new(condition),
+            consequence: BlockStatement {
+                token: Token::LBrace,
+                statements: vec![Statement::Expression(ExpressionStatement {
+                    token: Token::Ident("f".to_string()),
+                    expression: recursive_call,
+                    span: span(26, 34),
+                })],
+                span: span(24, 36),
+            },
+            alternative: Some(BlockStatement {
+                token: Token::LBrace,
+                statements: vec![Statement::Expression(ExpressionStatement {
+                    token: Token::True,
+                    expression: Expression::Boolean(Boolean {
+                        token: Token::True,
+                        value: true,
+                        span: span(44, 48),
+                    }),
+                    span: span(44, 48),
+                })],
+                span: span(42, 50),
+            }),
+            span: span(16, 50),
+        });
+        let expected = Program {
+            statements: vec![Statement::Let(LetStatement {
+                token: Token::Let,
+                identifier: f(4),
+                value: Expression::Function(FunctionLiteral {
+                    token: Token::Function,
+                    parameters: vec![x(11)],
+                    body: BlockStatement {
+                        token: Token::LBrace,
+                        statements: vec![Statement::Expression(ExpressionStatement {
+                            token: Token::If,
+                            expression: if_expression,
+                            span: span(16, 50),
+                        })],
+                        span: span(14, 52),
+                    },
+                    span: span(8, 52),
+                }),
+                doc: None,
+                span: span(0, 53),
+            })],
+            span: span(0, 53),
+        };
+
+        assert_eq!(program, expected);
+    }
+
+    #[test]
+    fn test_multiline_spans() {
+        let program = parse(
+            "let a = 1;
+let add = fn(x, y) {
+    x + y
+};
+add(a,
+    2);",
+        );
+
+        let spans: Vec<(usize, usize, usize, usize)> = program
+            .statements
+            .iter()
+            .map(|statement| {
+                let span = statement.span();
+                (span.line, span.column, span.start, span.end)
+            })
+            .collect();
+        assert_eq!(spans, [(1, 1, 0, 10), (2, 1, 11, 44), (5, 1, 45, 59)]);
+
+        let Statement::Let(let_statement) = &program.statements[1] else {
+            panic!("Could not extract LetStatement from Statement.")
+        };
+        let Expression::Function(function) = &let_statement.value else {
+            panic!("Could not extract FunctionLiteral from Expression.")
+        };
+        let body = function.body.statements[0].span();
+        assert_eq!(
+            (body.line, body.column, body.start, body.end),
+            (3, 5, 36, 41)
+        );
+    }
+
+    #[test]
+    fn test_error_location() {
+        let mut lexer = Lexer::new("let x = 5;\nlet = 10;");
+        let mut parser = Parser::new(&mut lexer);
+
+        parser.parse_program();
+
+        assert_eq!(
+            parser.get_errors()[0].to_string(),
+            "line 2, column 5: Expected next token to be Ident(), got Assign instead"
+        );
+    }
+
+    #[test]
+    fn test_missing_prefix_parse_fn() {
+        let mut lexer = Lexer::new("*5;");
+        let mut parser = Parser::new(&mut lexer);
+
+        parser.parse_program();
+
+        assert_eq!(
+            parser.get_errors()[0].to_string(),
+            "line 1, column 1: No prefix parse function for Asterisk found"
+        );
+    }
+
+    #[test]
+    fn test_string_literal_expression() {
+        let program = parse(r#""hello\tworld";"#);
+
+        assert_eq!(
+            *expression_of(&program.statements[0]),
+            Expression::String(StringLiteral {
+                token: Token::String("hello\tworld".to_string()),
+                value: "hello\tworld".to_string(),
+                span: Span {
+                    start: 0,
+                    end: 14,
+                    line: 1,
+                    column: 1
+                },
+            })
+        );
+        assert_eq!(program.to_string(), r#""hello\tworld""#);
+    }
+
+    #[test]
+    fn test_array_literal() {
+        let program = parse("[1, 2 * 2, 3 + 3]");
+
+        let array = match expression_of(&program.statements[0]) {
+            Expression::Array(array) => array,
+            other => panic!("Expected an ArrayLiteral, got {:?}", other),
+        };
+
+        assert_eq!(array.elements.len(), 3);
+        test_integer_literal(&array.elements[0], 1);
+        assert_eq!(array.elements[1].to_string(), "(2 * 2)");
+        assert_eq!(array.elements[2].to_string(), "(3 + 3)");
+        assert_eq!((array.span.start, array.span.end), (0, 17));
+
+        let program = parse("[]");
+        assert_eq!(program.to_string(), "[]");
+    }
+
+    #[test]
+    fn test_index_expression() {
+        let program = parse("my_array[1 + 1]");
+
+        let index = match expression_of(&program.statements[0]) {
+            Expression::Index(index) => index,
+            other => panic!("Expected an IndexExpression, got {:?}", other),
+        };
+
+        test_identifier(&index.left, "my_array");
+        assert_eq!(index.index.to_string(), "(1 + 1)");
+        assert_eq!((index.span.start, index.span.end), (0, 15));
+    }
+
+    #[test]
+    fn test_hash_literal() {
+        let tests = [
+            (
+                r#"{"one": 1, "two": 2, "three": 3}"#,
+                r#"{"one": 1, "two": 2, "three": 3}"#,
+            ),
+            ("{}", "{}"),
+            (r#"{true: 1 + 1, 2: "b",}"#, r#"{true: (1 + 1), 2: "b"}"#),
+            (
+                "if (x) { {x: 1} } else { {} }",
+                "if (x) { {x: 1} } else { {} }",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input);
+            assert_eq!(program.to_string(), expected, "Wrong parse for `{input}`");
+        }
+
+        let program = parse(r#"{"a": 1}"#);
+        let hash = match expression_of(&program.statements[0]) {
+            Expression::Hash(hash) => hash,
+            other => panic!("Expected a HashLiteral, got {:?}", other),
+        };
+        assert_eq!(hash.pairs.len(), 1);
+        test_integer_literal(&hash.pairs[0].1, 1);
+        assert_eq!((hash.span.start, hash.span.end), (0, 8));
+    }
+
+    #[test]
+    fn test_hash_literal_errors() {
+        let tests = [
+            (
+                r#"{"a" 1}"#,
+                "line 1, column 6: Expected next token to be Colon, got Int(1) instead",
+            ),
+            (
+                r#"{"a": 1 "b": 2}"#,
+                "line 1, column 9: Expected next token to be Comma, got String(b) instead",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(input);
+            let mut parser = Parser::new(&mut lexer);
+
+            parser.parse_program();
+
+            assert_eq!(
+                parser.get_errors()[0].to_string(),
+                expected,
+                "Wrong error for `{input}`"
+            );
+        }
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let program = parse(
+            "/// The answer
+let answer = 42;
+// not a doc
+let other = 1;
+/// Adds
+/// two numbers
+let add = fn(a, b) {
+    /// inner
+    let sum = a + b;
+    sum
+};
+/// ignored
+add(1, 2)",
+        );
+
+        let docs: Vec<Option<&str>> = program
+            .statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::Let(statement) => Some(statement.doc.as_deref()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            docs,
+            vec![Some("The answer"), None, Some("Adds\ntwo numbers")]
+        );
+
+        let Statement::Let(add) = &program.statements[2] else {
+            panic!("Expected a let statement");
+        };
+        let Expression::Function(function) = &add.value else {
+            panic!("Expected a function");
+        };
+        let Statement::Let(sum) = &function.body.statements[0] else {
+            panic!("Expected a let statement");
+        };
+        assert_eq!(sum.doc.as_deref(), Some("inner"));
+    }
+
+    #[test]
+    fn test_unterminated_comment() {
+        let mut lexer = Lexer::new("let x = 1; /* comment");
+        let mut parser = Parser::new(&mut lexer);
+
+        parser.parse_program();
+
+        let diagnostic = &parser.get_errors()[0];
+        assert_eq!(diagnostic.code, UNTERMINATED_COMMENT);
+        assert_eq!(
+            diagnostic.to_string(),
+            "line 1, column 12: Unterminated block comment"
+        );
+    }
+
+    #[test]
+    fn test_error_recovery() {
+        let tests = [
+            (
+                "let x 5;\nlet y = 10;\ny",
+                vec!["line 1, column 7: Expected next token to be Assign, got Int(5) instead"],
+                vec!["<error>", "let y = 10;", "y"],
+            ),
+            (
+                "let = 1; let a = 2; return ; a",
+                vec![
+                    "line 1, column 5: Expected next token to be Ident(), got Assign instead",
+                    "line 1, column 28: No prefix parse function for Semicolon found",
+                ],
+                vec!["<error>", "let a = 2;", "<error>", "a"],
+            ),
+            (
+                "let f = fn(x) { let = 1; x }; f(1)",
+                vec!["line 1, column 21: Expected next token to be Ident(), got Assign instead"],
+                vec!["let f = fn(x) { <error> x };", "f(1)"],
+            ),
+            (
+                "fn() { 1 + }; 2",
+                vec!["line 1, column 12: No prefix parse function for RSquirly found"],
+                vec!["fn() { <error> }", "2"],
+            ),
+            (
+                "1 }; 2",
+                vec!["line 1, column 3: No prefix parse function for RSquirly found"],
+                vec!["1", "<error>", "2"],
+            ),
+            (
+                "add(1, 2 let x = 3; x",
+                vec!["line 1, column 10: Expected next token to be Rparen, got Let instead"],
+                vec!["<error>", "let x = 3;", "x"],
+            ),
+        ];
+
+        for (input, expected_errors, expected_statements) in tests {
+            let mut lexer = Lexer::new(input);
+            let mut parser = Parser::new(&mut lexer);
+
+            let program = parser.parse_program();
+
+            let errors: Vec<String> = parser
+                .get_errors()
+                .iter()
+                .map(|error| error.to_string())
+                .collect();
+            assert_eq!(errors, expected_errors, "Wrong errors for `{input}`");
+
+            let statements: Vec<String> = program
+                .statements
+                .iter()
+                .map(|statement| statement.to_string())
+                .collect();
+            assert_eq!(
+                statements, expected_statements,
+                "Wrong statements for `{input}`"
+            );
+        }
+    }
+
+    #[test]
+    fn test_error_limit() {
+        let input = "let = 1;\n".repeat(30);
+        let mut lexer = Lexer::new(&input);
+        let mut parser = Parser::new(&mut lexer);
+
+        let program = parser.parse_program();
+
+        assert_eq!(parser.get_errors().len(), 20);
+        assert_eq!(program.statements.len(), 30);
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let nested = |levels| format!("{}1{}", "(".repeat(levels), ")".repeat(levels));
+
+        let tests = [
+            (nested(255), vec![]),
+            (nested(5000), vec![(1, 257)]),
+            ("(".repeat(5000), vec![(1, 257)]),
+            (format!("{}1", "-".repeat(5000)), vec![(1, 257)]),
+        ];
+
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(&input);
+            let mut parser = Parser::new(&mut lexer);
+            parser.parse_program();
+
+            let errors: Vec<(usize, usize)> = parser
+                .get_errors()
+                .iter()
+                .map(|error| {
+                    assert_eq!(error.code, NESTED_TOO_DEEPLY);
+                    (error.span.line, error.span.column)
+                })
+                .collect();
+            assert_eq!(errors, expected, "Wrong errors for `{:.20}...`", input);
+        }
+    }
+
+    #[test]
+    fn test_diagnostic_details() {
+        let span = |start, end, line, column| Span {
+            start,
+            end,
+            line,
+            column,
+        };
+
+        let mut lexer = Lexer::new("add(1,\n  [2 3)");
+        let mut parser = Parser::new(&mut lexer);
+        parser.parse_program();
+
+        let diagnostic = &parser.get_errors()[0];
+        assert_eq!(diagnostic.code, UNEXPECTED_TOKEN);
+        assert_eq!(diagnostic.span, span(12, 13, 2, 6));
+        assert_eq!(
+            diagnostic.labels,
+            vec![Label {
+                span: span(9, 10, 2, 3),
+                message: "`[` opened here".to_string()
+            }]
+        );
+
+        // blocks left open at the end of the input
+        for (input, open) in [
+            ("if (true) { 1", span(10, 11, 1, 11)),
+            ("fn(x) {\n  x", span(6, 7, 1, 7)),
+        ] {
+            let mut lexer = Lexer::new(input);
+            let mut parser = Parser::new(&mut lexer);
+            parser.parse_program();
+
+            let diagnostic = &parser.get_errors()[0];
+            assert_eq!(
+                diagnostic.message, "Expected next token to be RBrace, got Eof instead",
+                "Wrong error for `{input}`"
+            );
+            assert_eq!(
+                diagnostic.labels,
+                vec![Label {
+                    span: open,
+                    message: "`{` opened here".to_string()
+                }]
+            );
+        }
+
+        let mut lexer = Lexer::new("let x = 1 +");
+        let mut parser = Parser::new(&mut lexer);
+        parser.parse_program();
+
+        let diagnostic = &parser.get_errors()[0];
+        assert_eq!(diagnostic.code, EXPECTED_EXPRESSION);
+        assert_eq!(
+            diagnostic.help.as_deref(),
+            Some("the input ends before the expression is complete")
+        );
+
+        let mut lexer = Lexer::new(r#"let s = "a\qb";"#);
+        let mut parser = Parser::new(&mut lexer);
+        parser.parse_program();
+
+        let diagnostic = &parser.get_errors()[0];
+        assert_eq!(diagnostic.code, INVALID_STRING);
+        assert_eq!(diagnostic.span, span(8, 14, 1, 9));
+        assert!(diagnostic.help.is_some());
+    }
+
+    #[test]
+    fn test_integer_literal_forms() {
+        let tests = [
+            ("0x2A", 42, "0x2A"),
+            ("0o52", 42, "0o52"),
+            ("0b10_1010", 42, "0b10_1010"),
+            ("1_000", 1000, "1_000"),
+        ];
+
+        for (input, expected, printed) in tests {
+            let program = parse(input);
+            let Expression::Integer(integer) = expression_of(&program.statements[0]) else {
+                panic!("Expected an IntegerLiteral for `{input}`");
+            };
+            assert_eq!(integer.value, expected, "Wrong value for `{input}`");
+            assert_eq!(program.to_string(), printed, "Wrong printing for `{input}`");
+        }
+
+        // the smallest integer is the negation of a literal out of range
+        for input in ["-9223372036854775808", "-0x8000_0000_0000_0000"] {
+            let program = parse(input);
+            let Expression::Integer(integer) = expression_of(&program.statements[0]) else {
+                panic!("Expected an IntegerLiteral for `{input}`");
+            };
+            assert_eq!(integer.value, i64::MIN, "Wrong value for `{input}`");
+            assert_eq!(program.to_string(), input, "Wrong printing for `{input}`");
+        }
+        assert_eq!(
+            parse("1 - -9223372036854775808").to_string(),
+            "(1 - -9223372036854775808)"
+        );
+
+        for input in [
+            "9223372036854775808",
+            "-9223372036854775809",
+            "!9223372036854775808",
+        ] {
+            let mut lexer = Lexer::new(input);
+            let mut parser = Parser::new(&mut lexer);
+            parser.parse_program();
+            assert_eq!(
+                parser.get_errors()[0].code,
+                INVALID_NUMBER,
+                "Wrong error for `{input}`"
+            );
+        }
+    }
+
+    #[test]
+    fn test_float_literal() {
+        let tests = [
+            ("2.75", 2.75, "2.75"),
+            ("1e-9", 1e-9, "1e-9"),
+            ("1_000.5", 1000.5, "1_000.5"),
+            ("-2.5E3", 2500.0, "(-2.5E3)"),
+        ];
+
+        for (input, expected, printed) in tests {
+            let program = parse(input);
+            assert_eq!(program.to_string(), printed, "Wrong printing for `{input}`");
+
+            let float = match expression_of(&program.statements[0]) {
+                Expression::Float(float) => float,
+                Expression::Prefix(prefix) => match prefix.right.as_ref() {
+                    Expression::Float(float) => float,
+                    other => panic!("Expected a FloatLiteral, got {other:?}"),
+                },
+                other => panic!("Expected a FloatLiteral, got {other:?}"),
+            };
+            assert_eq!(float.value, expected, "Wrong value for `{input}`");
+        }
+
+        assert_eq!(
+            parse("1.5 + 2 * 0.25e1").to_string(),
+            "(1.5 + (2 * 0.25e1))"
+        );
+    }
+
+    #[test]
+    fn test_illegal_characters() {
+        let tests = [
+            ("let x = @;", "line 1, column 9: Illegal character `@`"),
+            ("let # = 5;", "line 1, column 5: Illegal character `#`"),
+            ("5 +\n  $", "line 2, column 3: Illegal character `$`"),
+            (
+                "let s = \"abc",
+                "line 1, column 9: Invalid string: unterminated string",
+            ),
+            (
+                "let x = 0b12;",
+                "line 1, column 9: Invalid number: invalid digit `2` in binary literal",
+            ),
+            (
+                "let x = .5;",
+                "line 1, column 9: Invalid number: missing digits before `.`",
+            ),
+            (
+                "2 * 1e400",
+                "line 1, column 5: Float literal 1e400 is out of range",
+            ),
+            (
+                "1 + 0x8000_0000_0000_0000",
+                "line 1, column 5: Integer literal 0x8000_0000_0000_0000 is out of range",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(input);
+            let mut parser = Parser::new(&mut lexer);
+
+            parser.parse_program();
+
+            assert_eq!(
+                parser.get_errors()[0].to_string(),
+                expected,
+                "Wrong error for `{input}`"
+            );
+        }
+    }
+
+    // ===========================================
+    // HELPERS
+    fn test_let_statement(expected: String, actual: &Statement) {
+        assert_eq!(actual.token_litteral(), "let".to_string());
+
+        let let_statement = if let Statement::Let(let_s) = actual {
+            let_s
+        } else {
+            panic!("Could not extract LetStatement from Statement.")
+        };
+
+        assert_eq!(
+            let_statement.identifier.value, expected,
+            "Value of identifier is wrong (expected: {}, actual: {})",
+            let_statement.identifier.value, expected
+        );
+
+        assert_eq!(
+            let_statement.identifier.token_litteral(),
+            expected,
+            "Value returned from token_litteral is wrong (expected: {}, actual: {})",
+            let_statement.identifier.token_litteral(),
+            expected
+        );
+    }
+
+    fn parse(input: &str) -> Program {
+        let mut lexer = Lexer::new(input);
+        let mut parser = Parser::new(&mut lexer);
+
+        let program = parser.parse_program();
+        check_parser_errors(&parser);
+
+        return program;
+    }
+
+    fn expression_of(statement: &Statement) -> &Expression {
+        if let Statement::Expression(expression_s) = statement {
+            return &expression_s.expression;
+        } else {
+            panic!("Could not extract ExpressionStatement from Statement.")
+        }
+    }
+
+    fn test_identifier(expression: &Expression, expected: &str) {
+        let identifier = if let Expression::Identifier(ident) = expression {
+            ident
+        } else {
+            panic!("Could not extract Identifier from Expression.")
+        };
+
+        assert_eq!(identifier.value, expected);
+        assert_eq!(identifier.token_litteral(), expected);
+    }
+
+    fn test_integer_literal(expression: &Expression, expected: i64) {
+        let integer = if let Expression::Integer(int) = expression {
+            int
+        } else {
+            panic!("Could not extract IntegerLiteral from Expression.")
+        };
+
+        assert_eq!(integer.value, expected);
+        assert_eq!(integer.token_litteral(), expected.to_string());
+    }
+
+    fn test_boolean(expression: &Expression, expected: bool) {
+        let boolean = if let Expression::Boolean(bool) = expression {
+            bool
+        } else {
+            panic!("Could not extract Boolean from Expression.")
+        };
+
+        assert_eq!(boolean.value, expected);
+        assert_eq!(boolean.token_litteral(), expected.to_string());
     }
 
     fn check_parser_errors(parser: &Parser) {
         let errors = parser.get_errors();
-        if errors.len() == 0 {
+        if errors.is_empty() {
             return;
         }
 
         let mut msg = format!("Parser had {} errors:", errors.len());
         for err in errors {
-            msg.push_str("\n");
-            msg.push_str(err);
+            msg.push('\n');
+            msg.push_str(&err.to_string());
         }
 
         panic!("{}", msg);
@@ -0,0 +1,129 @@
+use crate::evaluator::Env;
+use crate::evaluator::builtins::BUILTINS;
+use crate::lexer::{KEYWORDS, is_identifier_continue};
+use crate::repl::highlighter::InputHighlighter;
+use crate::repl::session::COMMANDS;
+
+use rustyline::completion::Completer;
+use rustyline::highlight::{CmdKind, Highlighter};
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper, Result};
+
+use std::borrow::Cow;
+
+/// Editor integration of the REPL
+pub struct ReplHelper {
+    // bindings of the eval mode, offered as completions
+    pub env: Env,
+
+    highlighter: InputHighlighter,
+}
+
+impl ReplHelper {
+    pub fn new(env: Env) -> Self {
+        return ReplHelper {
+            env,
+            highlighter: InputHighlighter::new(),
+        };
+    }
+
+    /// Start of the word being completed and the candidates for it
+    /// (`:commands` at the start of the line, otherwise keywords, builtins and bound names)
+    pub fn completions(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let before = &line[..pos];
+        let start = before
+            .char_indices()
+            .rev()
+            .take_while(|(_, ch)| is_identifier_continue(*ch))
+            .last()
+            .map_or(pos, |(index, _)| index);
+
+        let word = &line[start..pos];
+
+        let mut candidates: Vec<String> = if before[..start].trim_start() == ":" {
+            COMMANDS.iter().map(|command| command.to_string()).collect()
+        } else if word.is_empty() {
+            return (pos, Vec::new());
+        } else {
+            let keywords = KEYWORDS.iter().map(|(keyword, _)| keyword.to_string());
+            let builtins = BUILTINS.iter().map(|builtin| builtin.name.to_string());
+            let bindings = self
+                .env
+                .borrow()
+                .bindings()
+                .into_iter()
+                .map(|(name, _)| name);
+
+            keywords.chain(builtins).chain(bindings).collect()
+        };
+
+        candidates.retain(|candidate| candidate.starts_with(word));
+        candidates.sort();
+        candidates.dedup();
+
+        return (start, candidates);
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Result<(usize, Vec<String>)> {
+        return Ok(self.completions(line, pos));
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        return self.highlighter.highlight(line, pos);
+    }
+
+    fn highlight_char(&self, line: &str, pos: usize, kind: CmdKind) -> bool {
+        return self.highlighter.highlight_char(line, pos, kind);
+    }
+}
+
+// the continuation lines are handled by the REPL loop (see read_input)
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::ReplHelper;
+    use crate::evaluator::{Environment, Object};
+
+    #[test]
+    fn test_completions() {
+        let env = Environment::new();
+        env.borrow_mut().set("lenient", Object::Integer(1));
+        env.borrow_mut().set("total", Object::Integer(2));
+
+        let helper = ReplHelper::new(env);
+
+        let tests = [
+            ("le", 0, vec!["len", "lenient", "let"]),
+            ("1 + tot", 4, vec!["total"]),
+            ("re", 0, vec!["rest", "return"]),
+            ("fi", 0, vec!["first"]),
+            ("x + ", 4, vec![]),
+            ("nope", 0, vec![]),
+            (":b", 1, vec!["bytecode"]),
+            (":e", 1, vec!["env", "eval"]),
+            ("1 :e", 3, vec!["else"]),
+        ];
+
+        for (line, start, expected) in tests {
+            assert_eq!(
+                helper.completions(line, line.len()),
+                (start, expected.iter().map(|c| c.to_string()).collect()),
+                "Wrong completions for `{line}`"
+            );
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use crate::lexer::Lexer;
+use crate::token::{SpannedToken, Token};
+
+use rustyline::highlight::{CmdKind, Highlighter};
+
+use std::borrow::Cow;
+use std::cell::Cell;
+
+// ANSI styles
+const KEYWORD: &str = "\x1b[1;35m";
+const LITERAL: &str = "\x1b[33m";
+const STRING: &str = "\x1b[32m";
+const OPERATOR: &str = "\x1b[36m";
+const COMMENT: &str = "\x1b[2;37m";
+const ERROR: &str = "\x1b[1;4;31m";
+const MATCHING_BRACKET: &str = "\x1b[1;34m";
+const RESET: &str = "\x1b[0m";
+
+/// Colors the input by running the lexer over it, and shows the bracket matching the one
+/// under (or right before) the cursor
+pub struct InputHighlighter {
+    // false for the final rendering of a line, once it has been accepted
+    show_matching_bracket: Cell<bool>,
+}
+
+impl Default for InputHighlighter {
+    fn default() -> Self {
+        return InputHighlighter::new();
+    }
+}
+
+impl InputHighlighter {
+    pub fn new() -> Self {
+        return InputHighlighter {
+            show_matching_bracket: Cell::new(true),
+        };
+    }
+
+    /// Highlighted version of the line, `cursor` is the byte offset of the cursor if the
+    /// matching bracket should be shown
+    pub fn colorize(&self, line: &str, cursor: Option<usize>) -> String {
+        let tokens: Vec<SpannedToken> = Lexer::new(line)
+            .take_while(|spanned| spanned.token != Token::Eof)
+            .collect();
+
+        let matching = cursor.and_then(|cursor| matching_bracket(&tokens, cursor));
+
+        let mut buf = String::with_capacity(line.len());
+        let mut end = 0;
+
+        for (index, spanned) in tokens.iter().enumerate() {
+            let text = &line[spanned.span.start..spanned.span.end];
+
+            buf += &trivia(&line[end..spanned.span.start]);
+            end = spanned.span.end;
+
+            let style = if matching == Some(index) {
+                Some(MATCHING_BRACKET)
+            } else {
+                style(&spanned.token)
+            };
+
+            match style {
+                Some(style) => buf += &format!("{}{}{}", style, text, RESET),
+                None => buf += text,
+            }
+        }
+
+        buf += &trivia(&line[end..]);
+
+        return buf;
+    }
+}
+
+impl Highlighter for InputHighlighter {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        let cursor = if self.show_matching_bracket.get() {
+            Some(pos)
+        } else {
+            None
+        };
+
+        return Cow::Owned(self.colorize(line, cursor));
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, kind: CmdKind) -> bool {
+        self.show_matching_bracket
+            .set(kind != CmdKind::ForcedRefresh);
+
+        // any typed character can change the tokens (ex: closing a string)
+        return true;
+    }
+}
+
+/// Whitespace and comments between two tokens
+fn trivia(text: &str) -> String {
+    let comments = text.trim();
+    if comments.is_empty() {
+        return text.to_string();
+    }
+
+    let start = text.len() - text.trim_start().len();
+    let end = start + comments.len();
+
+    return format!(
+        "{}{}{}{}{}",
+        &text[..start],
+        COMMENT,
+        comments,
+        RESET,
+        &text[end..]
+    );
+}
+
+fn style(token: &Token) -> Option<&'static str> {
+    return match token {
+        Token::Function
+        | Token::Let
+        | Token::If
+        | Token::Else
+        | Token::Return
+        | Token::While
+        | Token::For
+        | Token::In
+        | Token::Break
+        | Token::Continue => Some(KEYWORD),
+        Token::True | Token::False | Token::Int(..) | Token::Float(_) => Some(LITERAL),
+        Token::String(_) => Some(STRING),
+        Token::Assign
+        | Token::PlusAssign
+        | Token::MinusAssign
+        | Token::AsteriskAssign
+        | Token::SlashAssign
+        | Token::Plus
+        | Token::Minus
+        | Token::Bang
+        | Token::Asterisk
+        | Token::Slash
+        | Token::Percent
+        | Token::LesserThan
+        | Token::GreaterThan
+        | Token::LesserEqual
+        | Token::GreaterEqual
+        | Token::Equal
+        | Token::NotEqual
+        | Token::And
+        | Token::Or => Some(OPERATOR),
+        Token::Illegal(_)
+        | Token::InvalidNumber(_)
+        | Token::InvalidString(_)
+        | Token::UnterminatedString => Some(ERROR),
+        Token::UnterminatedComment => Some(COMMENT),
+        _ => None,
+    };
+}
+
+/// Index of the token matching the bracket under the cursor (or the one right before it)
+fn matching_bracket(tokens: &[SpannedToken], cursor: usize) -> Option<usize> {
+    let under = tokens
+        .iter()
+        .position(|spanned| spanned.span.start == cursor && is_bracket(&spanned.token));
+    let before = tokens
+        .iter()
+        .position(|spanned| spanned.span.end == cursor && is_bracket(&spanned.token));
+
+    let index = under.or(before)?;
+    let (open, close) = match tokens[index].token {
+        Token::LParen | Token::RParen => (Token::LParen, Token::RParen),
+        Token::LBrace | Token::RBrace => (Token::LBrace, Token::RBrace),
+        _ => (Token::LBracket, Token::RBracket),
+    };
+
+    // walk towards the other bracket, skipping the nested pairs of the same kind
+    let candidates: Box<dyn Iterator<Item = usize>> = if tokens[index].token == open {
+        Box::new(index + 1..tokens.len())
+    } else {
+        Box::new((0..index).rev())
+    };
+
+    let mut depth = 0;
+    for candidate in candidates {
+        let token = &tokens[candidate].token;
+
+        if *token == tokens[index].token {
+            depth += 1;
+        } else if *token == open || *token == close {
+            if depth == 0 {
+                return Some(candidate);
+            }
+            depth -= 1;
+        }
+    }
+
+    return None;
+}
+
+fn is_bracket(token: &Token) -> bool {
+    return matches!(
+        token,
+        Token::LParen
+            | Token::RParen
+            | Token::LBrace
+            | Token::RBrace
+            | Token::LBracket
+            | Token::RBracket
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        COMMENT, ERROR, InputHighlighter, KEYWORD, LITERAL, MATCHING_BRACKET, OPERATOR, RESET,
+        STRING,
+    };
+
+    fn styled(style: &str, text: &str) -> String {
+        return format!("{}{}{}", style, text, RESET);
+    }
+
+    #[test]
+    fn test_colorize() {
+        let tests = [
+            ("", String::new()),
+            (
+                "let x = 5;",
+                format!(
+                    "{} x {} {};",
+                    styled(KEYWORD, "let"),
+                    styled(OPERATOR, "="),
+                    styled(LITERAL, "5")
+                ),
+            ),
+            (
+                "  if (!true) { \"a b\" }",
+                format!(
+                    "  {} ({}{}) {{ {} }}",
+                    styled(KEYWORD, "if"),
+                    styled(OPERATOR, "!"),
+                    styled(LITERAL, "true"),
+                    styled(STRING, "\"a b\"")
+                ),
+            ),
+            (
+                "x @ \"abc",
+                format!("x {} {}", styled(ERROR, "@"), styled(ERROR, "\"abc")),
+            ),
+            (
+                "x /* a */ + /* b ",
+                format!(
+                    "x {} {} {}",
+                    styled(COMMENT, "/* a */"),
+                    styled(OPERATOR, "+"),
+                    styled(COMMENT, "/* b ")
+                ),
+            ),
+            (
+                "1; // one ",
+                format!("{}; {} ", styled(LITERAL, "1"), styled(COMMENT, "// one")),
+            ),
+            (
+                "x += a && b",
+                format!(
+                    "x {} a {} b",
+                    styled(OPERATOR, "+="),
+                    styled(OPERATOR, "&&")
+                ),
+            ),
+            (
+                "été == 1 ",
+                format!("été {} {} ", styled(OPERATOR, "=="), styled(LITERAL, "1")),
+            ),
+        ];
+
+        let highlighter = InputHighlighter::new();
+
+        for (input, expected) in tests {
+            assert_eq!(
+                highlighter.colorize(input, None),
+                expected,
+                "Wrong highlighting for `{input}`"
+            );
+        }
+    }
+
+    #[test]
+    fn test_matching_bracket() {
+        let line = "f([a], (b))";
+        let bracket = |text| styled(MATCHING_BRACKET, text);
+
+        let tests = [
+            // bracket under the cursor
+            (1, format!("f([a], (b){}", bracket(")"))),
+            (2, format!("f([a{}, (b))", bracket("]"))),
+            (10, format!("f{}[a], (b))", bracket("("))),
+            // bracket right before the cursor
+            (11, format!("f{}[a], (b))", bracket("("))),
+            (8, format!("f([a], (b{})", bracket(")"))),
+            // no bracket around the cursor
+            (6, line.to_string()),
+        ];
+
+        let highlighter = InputHighlighter::new();
+
+        for (cursor, expected) in tests {
+            assert_eq!(
+                highlighter.colorize(line, Some(cursor)),
+                expected,
+                "Wrong matching bracket with the cursor at {cursor}"
+            );
+        }
+
+        // brackets in strings are not delimiters
+        assert_eq!(
+            highlighter.colorize("(\")\"", Some(0)),
+            format!("({}", styled(STRING, "\")\""))
+        );
+    }
+}
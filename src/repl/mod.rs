@@ -0,0 +1,105 @@
+pub mod helper;
+pub mod highlighter;
+pub mod session;
+pub mod validator;
+
+use helper::ReplHelper;
+use session::Session;
+use validator::InputValidator;
+
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::ValidationResult;
+use rustyline::{Editor, Result};
+
+use std::path::PathBuf;
+
+const PROMPT: &str = ">> ";
+const CONTINUATION_PROMPT: &str = ".. ";
+
+type ReplEditor = Editor<ReplHelper, DefaultHistory>;
+
+pub fn start() -> Result<()> {
+    let mut session = Session::new();
+    let validator = InputValidator::new();
+
+    let mut rl = ReplEditor::new()?;
+    rl.set_helper(Some(ReplHelper::new(session.env())));
+
+    let history = history_path();
+    if let Some(path) = &history {
+        // no history yet on the first run
+        let _ = rl.load_history(path);
+    }
+
+    println!("Hello! This is the Monkey Programming Language!");
+    println!("Feel free to type in commands (:help for the REPL commands).");
+
+    loop {
+        // `:reset` replaces the environment
+        if let Some(helper) = rl.helper_mut() {
+            helper.env = session.env();
+        }
+
+        let input = match read_input(&mut rl, &validator) {
+            Ok(input) => input,
+            Err(ReadlineError::Interrupted) => {
+                println!("CTRL-C");
+                continue;
+            }
+            Err(ReadlineError::Eof) => {
+                println!("CTRL-D");
+                break;
+            }
+            Err(err) => {
+                println!("Error: {:?}", err);
+                break;
+            }
+        };
+
+        rl.add_history_entry(input.as_str())?;
+
+        let output = session.execute(&input);
+        if !output.is_empty() {
+            println!("{output}");
+        }
+    }
+
+    if let Some(path) = &history {
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        if let Err(err) = rl.save_history(path) {
+            println!("Could not save the history: {}", err);
+        }
+    }
+
+    Ok(())
+}
+
+/// File the inputs are saved to between sessions
+/// ($XDG_DATA_HOME/monkey/history, defaults to ~/.local/share/monkey/history)
+fn history_path() -> Option<PathBuf> {
+    let data_dir = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/share"),
+    };
+
+    return Some(data_dir.join("monkey").join("history"));
+}
+
+/// Reads lines until the input is complete (balanced delimiters and closed strings)
+/// NOTE: rustyline can not show a prompt in front of the continuation lines of its own
+/// multi-line editing, so every line is read separately and checked by the validator
+fn read_input(rl: &mut ReplEditor, validator: &InputValidator) -> Result<String> {
+    let mut input = rl.readline(PROMPT)?;
+
+    while let ValidationResult::Incomplete = validator.check(&input) {
+        let line = rl.readline(CONTINUATION_PROMPT)?;
+
+        input.push('\n');
+        input.push_str(&line);
+    }
+
+    return Ok(input);
+}
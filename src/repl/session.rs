@@ -0,0 +1,277 @@
+use crate::ast::{Node, Program, Statement};
+use crate::compiler::{Compiler, SymbolTable};
+use crate::evaluator::{self, Env, Environment, Object};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+use std::rc::Rc;
+
+/// Pipeline stage the REPL input is run through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Tokens,
+    Ast,
+    Eval,
+    Bytecode,
+}
+
+impl Mode {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Mode::Tokens => "tokens",
+            Mode::Ast => "ast",
+            Mode::Eval => "eval",
+            Mode::Bytecode => "bytecode",
+        };
+    }
+}
+
+/// Names of the `:commands`
+pub const COMMANDS: [&str; 7] = ["tokens", "ast", "eval", "bytecode", "env", "reset", "help"];
+
+const HELP: &str = "Commands:
+  :tokens    print the tokens of each input
+  :ast       print the parsed program
+  :eval      evaluate each input (default)
+  :bytecode  print the compiled instructions and constants
+  :env       list the bindings of the eval environment
+  :reset     forget every binding
+  :help      show this message";
+
+/// State kept between two inputs of the REPL
+pub struct Session {
+    pub mode: Mode,
+
+    env: Env,
+
+    // compiler state, so that globals of previous inputs can be referred to
+    symbol_table: SymbolTable,
+    constants: Vec<Object>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        return Session::new();
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        let (symbol_table, constants) = Compiler::new().into_state();
+
+        return Session {
+            mode: Mode::Eval,
+            env: Environment::new(),
+            symbol_table,
+            constants,
+        };
+    }
+
+    /// Handle on the environment of the eval mode (replaced on `:reset`)
+    pub fn env(&self) -> Env {
+        return Rc::clone(&self.env);
+    }
+
+    /// Runs a line of input (or a `:command`) and returns what should be printed
+    pub fn execute(&mut self, input: &str) -> String {
+        let trimmed = input.trim();
+        if let Some(command) = trimmed.strip_prefix(':') {
+            return self.execute_command(command);
+        }
+
+        return match self.mode {
+            Mode::Tokens => tokens(input),
+            Mode::Ast => match parse(input) {
+                Ok(program) => program.to_string(),
+                Err(errors) => errors,
+            },
+            Mode::Eval => self.eval(input),
+            Mode::Bytecode => self.bytecode(input),
+        };
+    }
+
+    fn execute_command(&mut self, command: &str) -> String {
+        let mode = match command {
+            "tokens" => Mode::Tokens,
+            "ast" => Mode::Ast,
+            "eval" => Mode::Eval,
+            "bytecode" => Mode::Bytecode,
+            "env" => return self.list_bindings(),
+            "reset" => {
+                *self = Session {
+                    mode: self.mode,
+                    ..Session::new()
+                };
+                return "Every binding was removed".to_string();
+            }
+            "help" => return HELP.to_string(),
+            _ => {
+                return format!(
+                    "Unknown command `:{}`, type :help for the list of commands",
+                    command
+                );
+            }
+        };
+
+        self.mode = mode;
+        return format!("Switched to {} mode", mode.name());
+    }
+
+    fn eval(&mut self, input: &str) -> String {
+        let program = match parse(input) {
+            Ok(program) => program,
+            Err(errors) => return errors,
+        };
+
+        let result = evaluator::eval(&program, &self.env);
+
+        // `let`, assignments and loops have no interesting value to show
+        if result == Object::Null
+            && matches!(
+                program.statements.last(),
+                Some(
+                    Statement::Let(_)
+                        | Statement::Assign(_)
+                        | Statement::While(_)
+                        | Statement::For(_)
+                )
+            )
+        {
+            return String::new();
+        }
+
+        return result.to_string();
+    }
+
+    fn bytecode(&mut self, input: &str) -> String {
+        let program = match parse(input) {
+            Ok(program) => program,
+            Err(errors) => return errors,
+        };
+
+        let symbol_table = std::mem::take(&mut self.symbol_table);
+        let constants = std::mem::take(&mut self.constants);
+
+        let mut compiler = Compiler::new_with_state(symbol_table, constants);
+        let result = compiler.compile(&program);
+        let bytecode = compiler.bytecode();
+        (self.symbol_table, self.constants) = compiler.into_state();
+
+        if let Err(message) = result {
+            return format!("Compilation failed: {}", message);
+        }
+
+        return bytecode.disassemble();
+    }
+
+    fn list_bindings(&self) -> String {
+        let bindings: Vec<String> = self
+            .env
+            .borrow()
+            .bindings()
+            .iter()
+            .map(|(name, value)| format!("{} = {}", name, value))
+            .collect();
+
+        if bindings.is_empty() {
+            return "No bindings".to_string();
+        }
+
+        return bindings.join("\n");
+    }
+}
+
+fn tokens(input: &str) -> String {
+    let tokens: Vec<String> = Lexer::new(input)
+        .map(|spanned| spanned.token.to_string())
+        .collect();
+
+    return tokens.join(", ");
+}
+
+/// Parses the input, the errors are rendered in a printable message
+fn parse(input: &str) -> Result<Program, String> {
+    let mut lexer = Lexer::new(input);
+    let mut parser = Parser::new(&mut lexer);
+
+    let program = parser.parse_program();
+
+    if !parser.get_errors().is_empty() {
+        let diagnostics: Vec<String> = parser
+            .get_errors()
+            .iter()
+            .map(|diagnostic| diagnostic.render(input, "<repl>"))
+            .collect();
+
+        return Err(diagnostics.join("\n\n"));
+    }
+
+    return Ok(program);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Mode, Session};
+
+    #[test]
+    fn test_modes() {
+        let mut session = Session::new();
+        assert_eq!(session.mode, Mode::Eval);
+
+        assert_eq!(session.execute(":tokens"), "Switched to tokens mode");
+        assert_eq!(session.execute("let x"), "Let, Ident(x), Eof");
+
+        assert_eq!(session.execute(":ast"), "Switched to ast mode");
+        assert_eq!(session.execute("1 + 2 * 3"), "(1 + (2 * 3))");
+        assert_eq!(
+            session.execute("let x 5"),
+            "error[E0001]: Expected next token to be Assign, got Int(5) instead
+ --> <repl>:1:7
+  |
+1 | let x 5
+  |       ^"
+        );
+
+        assert_eq!(session.execute(":bytecode"), "Switched to bytecode mode");
+        assert_eq!(
+            session.execute("1 + 2"),
+            "0000 OpConstant 0\n0003 OpConstant 1\n0006 OpAdd\n0007 OpPop\nConstants:\n0000 1\n0001 2"
+        );
+
+        assert_eq!(
+            session.execute(":nope"),
+            "Unknown command `:nope`, type :help for the list of commands"
+        );
+    }
+
+    #[test]
+    fn test_eval_environment_persists() {
+        let mut session = Session::new();
+
+        assert_eq!(session.execute("let a = 5;"), "");
+        assert_eq!(session.execute("let b = a * 2;"), "");
+        assert_eq!(session.execute("a + b"), "15");
+        assert_eq!(session.execute("c"), "ERROR: identifier not found: c");
+        assert_eq!(session.execute(":env"), "a = 5\nb = 10");
+
+        assert_eq!(session.execute(":reset"), "Every binding was removed");
+        assert_eq!(session.execute(":env"), "No bindings");
+        assert_eq!(session.execute("a"), "ERROR: identifier not found: a");
+    }
+
+    #[test]
+    fn test_bytecode_globals_persist() {
+        let mut session = Session::new();
+        session.execute(":bytecode");
+
+        session.execute("let a = 1;");
+        assert_eq!(
+            session.execute("a"),
+            "0000 OpGetGlobal 0\n0003 OpPop\nConstants:\n0000 1"
+        );
+        assert_eq!(
+            session.execute("b"),
+            "Compilation failed: identifier not found: b"
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use crate::lexer::Lexer;
+use crate::token::Token;
+
+use rustyline::Result;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+
+/// Tells whether the input typed so far is a whole program or if more lines are needed
+/// (unclosed `(`, `{`, `[`, string or block comment)
+#[derive(Default)]
+pub struct InputValidator {}
+
+impl InputValidator {
+    pub fn new() -> Self {
+        return InputValidator {};
+    }
+
+    pub fn check(&self, input: &str) -> ValidationResult {
+        let mut open_delimiters = Vec::new();
+
+        for spanned in Lexer::new(input) {
+            let token = spanned.token;
+
+            match token {
+                Token::LParen | Token::LBrace | Token::LBracket => open_delimiters.push(token),
+                Token::RParen | Token::RBrace | Token::RBracket => {
+                    let expected = match open_delimiters.pop() {
+                        Some(Token::LParen) => Token::RParen,
+                        Some(Token::LBrace) => Token::RBrace,
+                        Some(Token::LBracket) => Token::RBracket,
+                        // nothing to wait for, the parser will report the stray delimiter
+                        _ => return ValidationResult::Valid(None),
+                    };
+
+                    if token != expected {
+                        return ValidationResult::Valid(None);
+                    }
+                }
+                // only the last token can be unterminated
+                Token::UnterminatedString | Token::UnterminatedComment => {
+                    return ValidationResult::Incomplete;
+                }
+                _ => {}
+            }
+        }
+
+        if open_delimiters.is_empty() {
+            return ValidationResult::Valid(None);
+        }
+
+        return ValidationResult::Incomplete;
+    }
+}
+
+impl Validator for InputValidator {
+    fn validate(&self, ctx: &mut ValidationContext) -> Result<ValidationResult> {
+        return Ok(self.check(ctx.input()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InputValidator;
+
+    use rustyline::validate::ValidationResult;
+
+    #[test]
+    fn test_check() {
+        let tests = [
+            ("", true),
+            ("let x = 5;", true),
+            ("let f = fn(x) {", false),
+            ("let f = fn(x) {\n  x + 1\n", false),
+            ("let f = fn(x) {\n  x + 1\n};", true),
+            ("[1, 2,", false),
+            ("add(1, [2, {\"a\": 3}]", false),
+            ("add(1, [2, {\"a\": 3}])", true),
+            ("\"abc", false),
+            ("\"abc\\\"", false),
+            ("\"abc\ndef\"", true),
+            ("\"{\"", true),
+            ("}", true),
+            ("(]", true),
+            ("1 /* a /* b */", false),
+            ("1 /* a /* b */\n*/", true),
+            ("1 // (", true),
+        ];
+
+        let validator = InputValidator::new();
+
+        for (input, complete) in tests {
+            let result = validator.check(input);
+            assert_eq!(
+                matches!(result, ValidationResult::Valid(_)),
+                complete,
+                "Wrong completeness for `{input}`"
+            );
+        }
+    }
+}
@@ -1,13 +1,53 @@
 use std::fmt::Display;
 
+/// Location of a piece of source code
+/// `start` and `end` are byte offsets (end excluded), `line` and `column` are 1-based and point at `start`
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    /// Creates a span starting at the beginning of self and ending at the end of other
+    pub fn to(&self, other: Span) -> Span {
+        return Span {
+            end: other.end,
+            ..*self
+        };
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "line {}, column {}", self.line, self.column);
+    }
+}
+
+/// Token paired with its location in the source
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+    pub doc: Option<String>, // `///` comments right before the token (trivia)
+}
+
 #[derive(Debug, Eq, PartialEq, Clone, Hash)]
 pub enum Token {
-    Illegal,
+    Illegal(String), // character the lexer does not know about
     Eof,
 
     // Identifiers and litterals
     Ident(String),
-    Int(String),
+    Int(Option<i64>, String), // value (None if it does not fit in an i64) and lexeme
+    Float(String),            // lexeme, already checked to be well formed
+    InvalidNumber(String),    // why the integer or float literal could not be read
+    String(String),           // value of the string, escape sequences already decoded
+    InvalidString(String),    // why the string literal could not be read
+    UnterminatedString,       // `"` without its closing quote, up to the end of the input
+    UnterminatedComment,      // `/*` without its `*/`, up to the end of the input
 
     // Operators
     Assign,
@@ -16,21 +56,36 @@ pub enum Token {
     Bang,
     Asterisk,
     Slash,
+    Percent,
 
     LesserThan,
     GreaterThan,
+    LesserEqual,
+    GreaterEqual,
 
     Equal,
     NotEqual,
 
+    And,
+    Or,
+
+    // Compound assignment
+    PlusAssign,
+    MinusAssign,
+    AsteriskAssign,
+    SlashAssign,
+
     // Delimiters
     Comma,
     Semicolon,
+    Colon,
 
     LParen,
     RParen,
     LBrace,
     RBrace,
+    LBracket,
+    RBracket,
 
     // Keywords
     Function,
@@ -40,31 +95,54 @@ pub enum Token {
     If,
     Else,
     Return,
+    While,
+    For,
+    In,
+    Break,
+    Continue,
 }
 
 impl Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         return match self {
-            Token::Illegal => write!(f, "Illegal"),
+            Token::Illegal(x) => write!(f, "Illegal({})", x),
             Token::Eof => write!(f, "Eof"),
             Token::Ident(x) => write!(f, "Ident({})", x),
-            Token::Int(x) => write!(f, "Int({})", x),
+            Token::Int(_, x) => write!(f, "Int({})", x),
+            Token::Float(x) => write!(f, "Float({})", x),
+            Token::InvalidNumber(x) => write!(f, "InvalidNumber({})", x),
+            Token::String(x) => write!(f, "String({})", x),
+            Token::InvalidString(x) => write!(f, "InvalidString({})", x),
+            Token::UnterminatedString => write!(f, "UnterminatedString"),
+            Token::UnterminatedComment => write!(f, "UnterminatedComment"),
             Token::Assign => write!(f, "Assign"),
             Token::Plus => write!(f, "Plus"),
             Token::Minus => write!(f, "Minus"),
             Token::Bang => write!(f, "Bang"),
             Token::Asterisk => write!(f, "Asterisk"),
             Token::Slash => write!(f, "Slash"),
+            Token::Percent => write!(f, "Percent"),
             Token::LesserThan => write!(f, "Lesserthan"),
             Token::GreaterThan => write!(f, "Greaterthan"),
+            Token::LesserEqual => write!(f, "LesserEqual"),
+            Token::GreaterEqual => write!(f, "GreaterEqual"),
             Token::Equal => write!(f, "Equal"),
             Token::NotEqual => write!(f, "NotEqual"),
+            Token::And => write!(f, "And"),
+            Token::Or => write!(f, "Or"),
+            Token::PlusAssign => write!(f, "PlusAssign"),
+            Token::MinusAssign => write!(f, "MinusAssign"),
+            Token::AsteriskAssign => write!(f, "AsteriskAssign"),
+            Token::SlashAssign => write!(f, "SlashAssign"),
             Token::Comma => write!(f, "Comma"),
             Token::Semicolon => write!(f, "Semicolon"),
+            Token::Colon => write!(f, "Colon"),
             Token::LParen => write!(f, "Lparen"),
             Token::RParen => write!(f, "Rparen"),
             Token::LBrace => write!(f, "LSquirly"),
             Token::RBrace => write!(f, "RSquirly"),
+            Token::LBracket => write!(f, "LBracket"),
+            Token::RBracket => write!(f, "RBracket"),
             Token::Function => write!(f, "Function"),
             Token::Let => write!(f, "Let"),
             Token::True => write!(f, "True"),
@@ -72,6 +150,11 @@ impl Display for Token {
             Token::If => write!(f, "If"),
             Token::Else => write!(f, "Else"),
             Token::Return => write!(f, "Return"),
+            Token::While => write!(f, "While"),
+            Token::For => write!(f, "For"),
+            Token::In => write!(f, "In"),
+            Token::Break => write!(f, "Break"),
+            Token::Continue => write!(f, "Continue"),
         };
     }
 }
@@ -79,33 +162,56 @@ impl Display for Token {
 impl Token {
     pub fn litteral(&self) -> String {
         let val = match self {
-            Token::Illegal => "ILLEGAL",
+            Token::Illegal(character) => character,
             Token::Eof => "",
-            Token::Ident(identifier) => &identifier,
-            Token::Int(int_val) => &int_val,
+            Token::Ident(identifier) => identifier,
+            Token::Int(_, lexeme) => lexeme,
+            Token::Float(lexeme) => lexeme,
+            Token::InvalidNumber(reason) => reason,
+            Token::String(value) => value,
+            Token::InvalidString(reason) => reason,
+            Token::UnterminatedString => "\"",
+            Token::UnterminatedComment => "/*",
             Token::Assign => "=",
             Token::Plus => "+",
             Token::Minus => "-",
             Token::Bang => "!",
             Token::Asterisk => "*",
             Token::Slash => "/",
+            Token::Percent => "%",
             Token::LesserThan => "<",
             Token::GreaterThan => ">",
+            Token::LesserEqual => "<=",
+            Token::GreaterEqual => ">=",
             Token::Equal => "==",
             Token::NotEqual => "!=",
+            Token::And => "&&",
+            Token::Or => "||",
+            Token::PlusAssign => "+=",
+            Token::MinusAssign => "-=",
+            Token::AsteriskAssign => "*=",
+            Token::SlashAssign => "/=",
             Token::Comma => ",",
-            Token::Semicolon => ",",
+            Token::Semicolon => ";",
+            Token::Colon => ":",
             Token::LParen => "(",
             Token::RParen => ")",
             Token::LBrace => "{",
             Token::RBrace => "}",
-            Token::Function => "function",
+            Token::LBracket => "[",
+            Token::RBracket => "]",
+            Token::Function => "fn",
             Token::Let => "let",
             Token::True => "true",
             Token::False => "false",
             Token::If => "if",
             Token::Else => "else",
             Token::Return => "return",
+            Token::While => "while",
+            Token::For => "for",
+            Token::In => "in",
+            Token::Break => "break",
+            Token::Continue => "continue",
         };
 
         return val.to_string();
@@ -0,0 +1,1062 @@
+use crate::code::{Instructions, Opcode, read_u8, read_u16};
+use crate::compiler::Bytecode;
+use crate::evaluator::builtins::BUILTINS;
+use crate::evaluator::{Closure, CompiledFunction, HashPair, Object};
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+pub const STACK_SIZE: usize = 2048;
+pub const GLOBALS_SIZE: usize = 65536;
+pub const MAX_FRAMES: usize = 1024;
+
+// ======================================================
+// Call Frame
+struct Frame {
+    closure: Rc<Closure>,
+    ip: usize,           // next instruction to execute
+    base_pointer: usize, // stack pointer before the call (locals are stored from there)
+}
+
+impl Frame {
+    fn instructions(&self) -> &Instructions {
+        return &self.closure.function.instructions;
+    }
+}
+
+// ======================================================
+// Virtual Machine
+pub struct Vm {
+    constants: Vec<Object>,
+
+    stack: Vec<Object>,
+    sp: usize, // always points to the next free slot, the top of the stack is stack[sp - 1]
+
+    globals: Vec<Object>,
+
+    frames: Vec<Frame>,
+
+    returned: bool, // the program was stopped by a top level `return`
+}
+
+impl Vm {
+    pub fn new(bytecode: Bytecode) -> Self {
+        return Vm::new_with_globals(bytecode, vec![Object::Null; GLOBALS_SIZE]);
+    }
+
+    /// Creates a vm that keeps the globals of a previous run
+    pub fn new_with_globals(bytecode: Bytecode, globals: Vec<Object>) -> Self {
+        // the program runs as the body of a function without parameters
+        let main_function = Rc::new(CompiledFunction {
+            instructions: bytecode.instructions,
+            num_locals: bytecode.num_locals,
+            num_parameters: 0,
+        });
+        let main_closure = Rc::new(Closure {
+            function: main_function,
+            free: Vec::new(),
+        });
+
+        let mut frames = Vec::with_capacity(MAX_FRAMES);
+        frames.push(Frame {
+            closure: main_closure,
+            ip: 0,
+            base_pointer: 0,
+        });
+
+        return Vm {
+            constants: bytecode.constants,
+            stack: vec![Object::Null; STACK_SIZE],
+            sp: bytecode.num_locals,
+            globals,
+            frames,
+            returned: false,
+        };
+    }
+
+    /// Gives back the globals so that a later run can reuse them
+    pub fn into_globals(self) -> Vec<Object> {
+        return self.globals;
+    }
+
+    /// Value of the last expression statement that was executed (or of the top level `return`)
+    pub fn last_popped_stack_elem(&self) -> &Object {
+        return &self.stack[self.sp];
+    }
+
+    /// Whether the program was stopped by a top level `return`
+    pub fn returned(&self) -> bool {
+        return self.returned;
+    }
+
+    pub fn run(&mut self) -> Result<(), String> {
+        while self.current_frame().ip < self.current_frame().instructions().len() {
+            let op = self.read_opcode()?;
+
+            match op {
+                Opcode::Constant => {
+                    let constant = self.read_operand(2);
+                    self.push(self.constants[constant].clone())?;
+                }
+                Opcode::Pop => {
+                    self.pop();
+                }
+                Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Div | Opcode::Mod => {
+                    self.execute_binary_operation(op)?;
+                }
+                Opcode::Equal
+                | Opcode::NotEqual
+                | Opcode::GreaterThan
+                | Opcode::LessThan
+                | Opcode::GreaterEqual
+                | Opcode::LessEqual => {
+                    self.execute_comparison(op)?;
+                }
+                Opcode::True => self.push(Object::Boolean(true))?,
+                Opcode::False => self.push(Object::Boolean(false))?,
+                Opcode::Null => self.push(Object::Null)?,
+                Opcode::Bang => {
+                    let operand = self.pop();
+                    self.push(Object::Boolean(!is_truthy(&operand)))?;
+                }
+                Opcode::Minus => {
+                    let result = match self.pop() {
+                        Object::Integer(value) => match value.checked_neg() {
+                            Some(negated) => Object::Integer(negated),
+                            None => return Err(format!("integer overflow: -{}", value)),
+                        },
+                        Object::Float(value) => Object::Float(-value),
+                        operand => {
+                            return Err(format!("unknown operator: -{}", operand.object_type()));
+                        }
+                    };
+
+                    self.push(result)?;
+                }
+                Opcode::Jump => {
+                    let target = self.read_operand(2);
+                    self.current_frame_mut().ip = target;
+                }
+                Opcode::JumpNotTruthy => {
+                    let target = self.read_operand(2);
+
+                    let condition = self.pop();
+                    if !is_truthy(&condition) {
+                        self.current_frame_mut().ip = target;
+                    }
+                }
+                Opcode::IterNext => {
+                    let target = self.read_operand(2);
+
+                    let Object::Integer(index) = self.pop() else {
+                        unreachable!("The position in a for loop is an integer");
+                    };
+                    let elements = match self.pop() {
+                        Object::Array(elements) => elements,
+                        other => {
+                            return Err(format!("cannot iterate over {}", other.object_type()));
+                        }
+                    };
+
+                    match elements.get(index as usize) {
+                        Some(element) => self.push(element.clone())?,
+                        None => self.current_frame_mut().ip = target,
+                    }
+                }
+                Opcode::SetGlobal => {
+                    let index = self.read_operand(2);
+                    self.globals[index] = self.pop();
+                }
+                Opcode::GetGlobal => {
+                    let index = self.read_operand(2);
+                    self.push(self.globals[index].clone())?;
+                }
+                Opcode::SetLocal => {
+                    let index = self.read_operand(1);
+                    let base_pointer = self.current_frame().base_pointer;
+                    self.stack[base_pointer + index] = self.pop();
+                }
+                Opcode::AssignLocal => {
+                    let index = self.read_operand(1);
+                    let base_pointer = self.current_frame().base_pointer;
+
+                    let value = self.pop();
+                    match &self.stack[base_pointer + index] {
+                        Object::Cell(cell) => *cell.borrow_mut() = value,
+                        _ => self.stack[base_pointer + index] = value,
+                    }
+                }
+                Opcode::GetLocal => {
+                    let index = self.read_operand(1);
+                    let base_pointer = self.current_frame().base_pointer;
+                    self.push(value_of(&self.stack[base_pointer + index]))?;
+                }
+                Opcode::CaptureLocal => {
+                    let index = self.read_operand(1);
+                    let base_pointer = self.current_frame().base_pointer;
+
+                    // the variable moves to a cell that the frame and the closures share
+                    let slot = &mut self.stack[base_pointer + index];
+                    if !matches!(slot, Object::Cell(_)) {
+                        let value = std::mem::replace(slot, Object::Null);
+                        *slot = Object::Cell(Rc::new(RefCell::new(value)));
+                    }
+
+                    self.push(self.stack[base_pointer + index].clone())?;
+                }
+                Opcode::GetFree => {
+                    let index = self.read_operand(1);
+                    let value = value_of(&self.current_frame().closure.free[index]);
+                    self.push(value)?;
+                }
+                Opcode::AssignFree => {
+                    let index = self.read_operand(1);
+
+                    let value = self.pop();
+                    match &self.current_frame().closure.free[index] {
+                        Object::Cell(cell) => *cell.borrow_mut() = value,
+                        _ => unreachable!("Assigned free variables are captured in cells"),
+                    }
+                }
+                Opcode::CaptureFree => {
+                    let index = self.read_operand(1);
+                    let variable = self.current_frame().closure.free[index].clone();
+                    self.push(variable)?;
+                }
+                Opcode::GetBuiltin => {
+                    let index = self.read_operand(1);
+                    self.push(Object::Builtin(BUILTINS[index]))?;
+                }
+                Opcode::Array => {
+                    let num_elements = self.read_operand(2);
+
+                    let elements = self.stack[self.sp - num_elements..self.sp].to_vec();
+                    self.sp -= num_elements;
+
+                    self.push(Object::Array(elements))?;
+                }
+                Opcode::Hash => {
+                    let num_elements = self.read_operand(2);
+                    self.build_hash(num_elements)?;
+                }
+                Opcode::Index => {
+                    let index = self.pop();
+                    let left = self.pop();
+                    self.execute_index_expression(left, index)?;
+                }
+                Opcode::CurrentClosure => {
+                    let closure = Rc::clone(&self.current_frame().closure);
+                    self.push(Object::Closure(closure))?;
+                }
+                Opcode::Closure => {
+                    let constant = self.read_operand(2);
+                    let num_free = self.read_operand(1);
+                    self.push_closure(constant, num_free)?;
+                }
+                Opcode::Call => {
+                    let num_arguments = self.read_operand(1);
+                    self.call_function(num_arguments)?;
+                }
+                Opcode::ReturnValue => {
+                    let return_value = self.pop();
+
+                    // `return` at the top level stops the program
+                    if self.frames.len() == 1 {
+                        self.returned = true;
+                        return Ok(());
+                    }
+
+                    let frame = self.frames.pop().expect("There is always a frame");
+                    self.sp = frame.base_pointer - 1; // also drops the function being called
+                    self.push(return_value)?;
+                }
+                Opcode::Return => {
+                    let frame = self.frames.pop().expect("There is always a frame");
+                    self.sp = frame.base_pointer - 1;
+                    self.push(Object::Null)?;
+                }
+            }
+        }
+
+        return Ok(());
+    }
+
+    // ======================================================
+    // Operations
+
+    fn execute_binary_operation(&mut self, op: Opcode) -> Result<(), String> {
+        let right = self.pop();
+        let left = self.pop();
+
+        if let Some((left, right)) = Object::float_operands(&left, &right) {
+            let result = match op {
+                Opcode::Add => left + right,
+                Opcode::Sub => left - right,
+                Opcode::Mul => left * right,
+                Opcode::Div => left / right,
+                Opcode::Mod => left % right,
+                _ => unreachable!("{op} is not a binary operation"),
+            };
+            return self.push(Object::Float(result));
+        }
+
+        let (left, right) = match (&left, &right) {
+            (Object::Integer(left), Object::Integer(right)) => (*left, *right),
+            (Object::String(left), Object::String(right)) if op == Opcode::Add => {
+                return self.push(Object::String(format!("{}{}", left, right)));
+            }
+            _ => return Err(operand_error(op, &left, &right)),
+        };
+
+        let result = match op {
+            Opcode::Add => left.checked_add(right),
+            Opcode::Sub => left.checked_sub(right),
+            Opcode::Mul => left.checked_mul(right),
+            Opcode::Div => {
+                if right == 0 {
+                    return Err("division by zero".to_string());
+                }
+                left.checked_div(right)
+            }
+            Opcode::Mod => {
+                if right == 0 {
+                    return Err("division by zero".to_string());
+                }
+                left.checked_rem(right)
+            }
+            _ => unreachable!("{op} is not a binary operation"),
+        };
+
+        return match result {
+            Some(value) => self.push(Object::Integer(value)),
+            None => Err(format!(
+                "integer overflow: {} {} {}",
+                left,
+                operator_of(op),
+                right
+            )),
+        };
+    }
+
+    fn execute_comparison(&mut self, op: Opcode) -> Result<(), String> {
+        let right = self.pop();
+        let left = self.pop();
+
+        if let Some((left, right)) = Object::float_operands(&left, &right) {
+            let result = match op {
+                Opcode::Equal => left == right,
+                Opcode::NotEqual => left != right,
+                Opcode::GreaterThan => left > right,
+                Opcode::LessThan => left < right,
+                Opcode::GreaterEqual => left >= right,
+                Opcode::LessEqual => left <= right,
+                _ => unreachable!("{op} is not a comparison"),
+            };
+            return self.push(Object::Boolean(result));
+        }
+
+        let result = match (op, &left, &right) {
+            (Opcode::Equal, Object::Integer(l), Object::Integer(r)) => l == r,
+            (Opcode::NotEqual, Object::Integer(l), Object::Integer(r)) => l != r,
+            (Opcode::GreaterThan, Object::Integer(l), Object::Integer(r)) => l > r,
+            (Opcode::LessThan, Object::Integer(l), Object::Integer(r)) => l < r,
+            (Opcode::GreaterEqual, Object::Integer(l), Object::Integer(r)) => l >= r,
+            (Opcode::LessEqual, Object::Integer(l), Object::Integer(r)) => l <= r,
+            (Opcode::Equal, Object::Boolean(l), Object::Boolean(r)) => l == r,
+            (Opcode::NotEqual, Object::Boolean(l), Object::Boolean(r)) => l != r,
+            (Opcode::Equal, Object::String(l), Object::String(r)) => l == r,
+            (Opcode::NotEqual, Object::String(l), Object::String(r)) => l != r,
+            _ => return Err(operand_error(op, &left, &right)),
+        };
+
+        return self.push(Object::Boolean(result));
+    }
+
+    fn push_closure(&mut self, constant: usize, num_free: usize) -> Result<(), String> {
+        let function = match &self.constants[constant] {
+            Object::CompiledFunction(function) => Rc::clone(function),
+            other => return Err(format!("not a function: {}", other.object_type())),
+        };
+
+        let free = self.stack[self.sp - num_free..self.sp].to_vec();
+        self.sp -= num_free;
+
+        return self.push(Object::Closure(Rc::new(Closure { function, free })));
+    }
+
+    /// Builds a hash out of the keys and values at the top of the stack
+    fn build_hash(&mut self, num_elements: usize) -> Result<(), String> {
+        let mut pairs = BTreeMap::new();
+
+        for pair in self.stack[self.sp - num_elements..self.sp].chunks_exact(2) {
+            let (key, value) = (pair[0].clone(), pair[1].clone());
+            pairs.insert(key.hash_key()?, HashPair { key, value });
+        }
+        self.sp -= num_elements;
+
+        return self.push(Object::Hash(pairs));
+    }
+
+    /// Out of bounds indexes and missing keys give null
+    fn execute_index_expression(&mut self, left: Object, index: Object) -> Result<(), String> {
+        return match (&left, &index) {
+            (Object::Hash(pairs), _) => {
+                let value = pairs
+                    .get(&index.hash_key()?)
+                    .map(|pair| pair.value.clone())
+                    .unwrap_or(Object::Null);
+
+                self.push(value)
+            }
+            (Object::Array(elements), Object::Integer(index)) => {
+                let element = usize::try_from(*index)
+                    .ok()
+                    .and_then(|index| elements.get(index))
+                    .cloned()
+                    .unwrap_or(Object::Null);
+
+                self.push(element)
+            }
+            _ => Err(format!(
+                "index operator not supported: {}[{}]",
+                left.object_type(),
+                index.object_type()
+            )),
+        };
+    }
+
+    /// Calls the function sitting below the arguments on the stack
+    fn call_function(&mut self, num_arguments: usize) -> Result<(), String> {
+        let closure = match &self.stack[self.sp - 1 - num_arguments] {
+            Object::Closure(closure) => Rc::clone(closure),
+            Object::Builtin(builtin) => {
+                let result = (builtin.function)(&self.stack[self.sp - num_arguments..self.sp])?;
+
+                self.sp -= num_arguments + 1; // also drops the function being called
+                return self.push(result);
+            }
+            other => return Err(format!("not a function: {}", other.object_type())),
+        };
+
+        if closure.function.num_parameters != num_arguments {
+            return Err(format!(
+                "wrong number of arguments: expected {}, got {}",
+                closure.function.num_parameters, num_arguments
+            ));
+        }
+
+        if self.frames.len() >= MAX_FRAMES {
+            return Err("stack overflow".to_string());
+        }
+
+        // the arguments become the first locals of the function
+        let base_pointer = self.sp - num_arguments;
+        let num_locals = closure.function.num_locals;
+
+        if base_pointer + num_locals >= STACK_SIZE {
+            return Err("stack overflow".to_string());
+        }
+
+        self.frames.push(Frame {
+            closure,
+            ip: 0,
+            base_pointer,
+        });
+        self.sp = base_pointer + num_locals;
+
+        return Ok(());
+    }
+
+    // ======================================================
+    // Helpers
+
+    fn current_frame(&self) -> &Frame {
+        return self.frames.last().expect("There is always a frame");
+    }
+
+    fn current_frame_mut(&mut self) -> &mut Frame {
+        return self.frames.last_mut().expect("There is always a frame");
+    }
+
+    fn read_opcode(&mut self) -> Result<Opcode, String> {
+        let frame = self.current_frame_mut();
+        let byte = frame.instructions()[frame.ip];
+        frame.ip += 1;
+
+        return Opcode::try_from(byte);
+    }
+
+    /// Reads an operand of the given width at the instruction pointer and advances it
+    fn read_operand(&mut self, width: usize) -> usize {
+        let frame = self.current_frame_mut();
+        let ip = frame.ip;
+
+        let value = match width {
+            2 => read_u16(&frame.instructions()[ip..]),
+            1 => read_u8(&frame.instructions()[ip..]),
+            _ => unreachable!("There is no operand of width {width}"),
+        };
+
+        frame.ip += width;
+
+        return value;
+    }
+
+    fn push(&mut self, object: Object) -> Result<(), String> {
+        if self.sp >= STACK_SIZE {
+            return Err("stack overflow".to_string());
+        }
+
+        self.stack[self.sp] = object;
+        self.sp += 1;
+
+        return Ok(());
+    }
+
+    /// NOTE: the popped value stays in the stack slot (see last_popped_stack_elem)
+    fn pop(&mut self) -> Object {
+        self.sp -= 1;
+        return self.stack[self.sp].clone();
+    }
+}
+
+/// Value of a variable, captured variables are read through their cell
+fn value_of(variable: &Object) -> Object {
+    return match variable {
+        Object::Cell(cell) => cell.borrow().clone(),
+        value => value.clone(),
+    };
+}
+
+fn is_truthy(object: &Object) -> bool {
+    return match object {
+        Object::Null => false,
+        Object::Boolean(value) => *value,
+        _ => true,
+    };
+}
+
+fn operator_of(op: Opcode) -> &'static str {
+    return match op {
+        Opcode::Add => "+",
+        Opcode::Sub => "-",
+        Opcode::Mul => "*",
+        Opcode::Div => "/",
+        Opcode::Mod => "%",
+        Opcode::Equal => "==",
+        Opcode::NotEqual => "!=",
+        Opcode::GreaterThan => ">",
+        Opcode::LessThan => "<",
+        Opcode::GreaterEqual => ">=",
+        Opcode::LessEqual => "<=",
+        _ => unreachable!("{op} is not an infix operation"),
+    };
+}
+
+/// Same messages as the evaluator for operands of the wrong type
+fn operand_error(op: Opcode, left: &Object, right: &Object) -> String {
+    if left.object_type() != right.object_type() {
+        return format!(
+            "type mismatch: {} {} {}",
+            left.object_type(),
+            operator_of(op),
+            right.object_type()
+        );
+    }
+
+    return format!(
+        "unknown operator: {} {} {}",
+        left.object_type(),
+        operator_of(op),
+        right.object_type()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Object, Vm};
+    use crate::compiler::Compiler;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_integer_arithmetic() {
+        let tests = [
+            ("1", 1),
+            ("1 + 2", 3),
+            ("1 - 2", -1),
+            ("4 / 2", 2),
+            ("50 / 2 * 2 + 10 - 5", 55),
+            ("5 * (2 + 10)", 60),
+            ("-50 + 100 + -50", 0),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", 50),
+            ("7 % 3", 1),
+            ("-7 % 3", -1),
+            ("2 + 10 % 4 * 3", 8),
+            ("-9223372036854775808 + 1", -9223372036854775807),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                run(input),
+                Ok(Object::Integer(expected)),
+                "Wrong result for `{input}`"
+            );
+        }
+    }
+
+    #[test]
+    fn test_boolean_expressions() {
+        let tests = [
+            ("true", true),
+            ("1 < 2", true),
+            ("1 > 2", false),
+            ("1 == 1", true),
+            ("1 != 1", false),
+            ("true != false", true),
+            ("(1 < 2) == true", true),
+            ("!true", false),
+            ("!!5", true),
+            ("!(if (false) { 5; })", true),
+            ("2 <= 2", true),
+            ("3 <= 2", false),
+            ("1 >= 2", false),
+            ("2 >= 2", true),
+            ("true && false", false),
+            ("false || true", true),
+            ("1 && \"\"", true),
+            ("1 < 2 && 2 < 3", true),
+            ("false || 1 > 2", false),
+            ("false && 1 / 0", false),
+            ("true || 1 / 0", true),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                run(input),
+                Ok(Object::Boolean(expected)),
+                "Wrong result for `{input}`"
+            );
+        }
+    }
+
+    #[test]
+    fn test_float_expressions() {
+        let tests = [
+            ("2.5", Object::Float(2.5)),
+            ("-0.5", Object::Float(-0.5)),
+            ("1.5 + 2.25", Object::Float(3.75)),
+            ("1 + 0.5", Object::Float(1.5)),
+            ("0.5 * 4", Object::Float(2.0)),
+            ("7 / 2.0", Object::Float(3.5)),
+            ("7.5 % 2", Object::Float(1.5)),
+            ("1 / 0.0", Object::Float(f64::INFINITY)),
+            ("-1 / 0.0", Object::Float(f64::NEG_INFINITY)),
+            ("1.5 < 2", Object::Boolean(true)),
+            ("2 >= 2.0", Object::Boolean(true)),
+            ("1 == 1.0", Object::Boolean(true)),
+            ("0.1 + 0.2 == 0.3", Object::Boolean(false)),
+            // NaN is unordered and never equal, even to itself
+            ("let nan = 0.0 / 0.0; nan == nan", Object::Boolean(false)),
+            ("let nan = 0.0 / 0.0; nan != nan", Object::Boolean(true)),
+            (
+                "let nan = 0.0 / 0.0; nan < 1 || nan >= 1",
+                Object::Boolean(false),
+            ),
+            ("int(7.9) + int(\"-2\")", Object::Integer(5)),
+            ("float(1) / 4", Object::Float(0.25)),
+            ("float(\"NaN\") == float(\"NaN\")", Object::Boolean(false)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Ok(expected), "Wrong result for `{input}`");
+        }
+    }
+
+    #[test]
+    fn test_conditionals() {
+        let tests = [
+            ("if (true) { 10 }", Object::Integer(10)),
+            ("if (1 > 2) { 10 } else { 20 }", Object::Integer(20)),
+            ("if (1 > 2) { 10 }", Object::Null),
+            ("if (false) { 10 }", Object::Null),
+            (
+                "if ((if (false) { 10 })) { 10 } else { 20 }",
+                Object::Integer(20),
+            ),
+            ("if (true) { let a = 5; }", Object::Null),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Ok(expected), "Wrong result for `{input}`");
+        }
+    }
+
+    #[test]
+    fn test_string_expressions() {
+        let tests = [
+            (r#""monkey""#, Object::String("monkey".to_string())),
+            (r#""mon" + "key""#, Object::String("monkey".to_string())),
+            (
+                r#""mon" + "key" + "banana""#,
+                Object::String("monkeybanana".to_string()),
+            ),
+            (r#""a" == "a""#, Object::Boolean(true)),
+            (r#""a" != "a""#, Object::Boolean(false)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Ok(expected), "Wrong result for `{input}`");
+        }
+    }
+
+    #[test]
+    fn test_array_expressions() {
+        let tests = [
+            ("[]", Object::Array(vec![])),
+            (
+                "[1, 2 * 2, 3 + 3]",
+                Object::Array(vec![
+                    Object::Integer(1),
+                    Object::Integer(4),
+                    Object::Integer(6),
+                ]),
+            ),
+            ("[1, 2, 3][1 + 1]", Object::Integer(3)),
+            ("[[1, 1, 1]][0][0]", Object::Integer(1)),
+            ("[1, 2, 3][3]", Object::Null),
+            ("[1][-1]", Object::Null),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Ok(expected), "Wrong result for `{input}`");
+        }
+    }
+
+    #[test]
+    fn test_hash_literals() {
+        let tests = [
+            ("{}", "{}"),
+            ("{1: 2, 2: 3}", "{1: 2, 2: 3}"),
+            (r#"{"b" + "": 2 * 2, true: 1}"#, "{true: 1, b: 4}"),
+        ];
+
+        for (input, expected) in tests {
+            let result = run(input).expect("Program should run");
+            assert_eq!(result.to_string(), expected, "Wrong result for `{input}`");
+        }
+    }
+
+    #[test]
+    fn test_hash_index_expressions() {
+        let tests = [
+            ("{1: 1, 2: 2}[1]", Object::Integer(1)),
+            ("{1: 1, 2: 2}[2]", Object::Integer(2)),
+            ("{1: 1}[0]", Object::Null),
+            ("{}[0]", Object::Null),
+            (r#"let h = {"a": [1, 2]}; h["a"][1]"#, Object::Integer(2)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Ok(expected), "Wrong result for `{input}`");
+        }
+    }
+
+    #[test]
+    fn test_builtin_functions() {
+        let tests = [
+            (r#"len("four")"#, Object::Integer(4)),
+            ("len([1, 2, 3])", Object::Integer(3)),
+            ("first([1, 2, 3])", Object::Integer(1)),
+            ("last([])", Object::Null),
+            (
+                "rest([1, 2, 3])",
+                Object::Array(vec![Object::Integer(2), Object::Integer(3)]),
+            ),
+            (
+                "let a = [1]; let b = push(a, 2); len(a) + len(b)",
+                Object::Integer(3),
+            ),
+            ("let len = fn(x) { 42 }; len([])", Object::Integer(42)),
+            (
+                "let f = fn(arr) { fn() { first(arr) } }; f([7])()",
+                Object::Integer(7),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Ok(expected), "Wrong result for `{input}`");
+        }
+    }
+
+    #[test]
+    fn test_global_let_statements() {
+        let tests = [
+            ("let one = 1; one", 1),
+            ("let one = 1; let two = 2; one + two", 3),
+            ("let one = 1; let two = one + one; one + two", 3),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                run(input),
+                Ok(Object::Integer(expected)),
+                "Wrong result for `{input}`"
+            );
+        }
+    }
+
+    #[test]
+    fn test_assign_statements() {
+        let tests = [
+            ("let a = 5; a += 2; a;", 7),
+            ("let a = 5; a -= 2; a;", 3),
+            ("let a = 5; a *= 2; a *= 3; a;", 30),
+            ("let a = 9; a /= 2; a;", 4),
+            ("let f = fn(x) { x += 10; let y = x; y *= 2; y }; f(1);", 22),
+            (
+                "let total = 0; let add = fn(n) { total += n; }; add(2); add(3); total;",
+                5,
+            ),
+            ("let f = fn() { f = 1; }; f(); f", 1),
+            // captured variables are shared with the scope they come from
+            (
+                "let counter = fn() { let c = 0; fn() { c += 1; c } }; let next = counter(); next(); next(); next()",
+                3,
+            ),
+            (
+                "let f = fn() { let x = 1; let g = fn() { x }; x = 5; g() }; f()",
+                5,
+            ),
+            (
+                "let f = fn() { let x = 0; let g = fn() { fn() { x += 1; } }; let h = g(); h(); h(); x }; f()",
+                2,
+            ),
+            ("let x = 1; let f = fn() { x }; let x = 2; f()", 2),
+            (
+                "let f = fn() { let x = 1; let g = fn() { x }; let x = 2; g() }; f()",
+                2,
+            ),
+            (
+                "let f = fn() { let g = fn() { g = 5; 1 }; g() + g }; f()",
+                6,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                run(input),
+                Ok(Object::Integer(expected)),
+                "Wrong result for `{input}`"
+            );
+        }
+    }
+
+    #[test]
+    fn test_loops() {
+        let tests = [
+            ("let x = 1; x = x + 1; x", Object::Integer(2)),
+            (
+                "let i = 0; while (i < 5) { i = i + 1; } i",
+                Object::Integer(5),
+            ),
+            (
+                "let i = 0; while (true) { i += 1; if (i == 3) { break; } } i",
+                Object::Integer(3),
+            ),
+            (
+                "let i = 0; let odd = 0; while (i < 10) { i += 1; if (i % 2 == 0) { continue; } odd += 1; } odd",
+                Object::Integer(5),
+            ),
+            (
+                "let sum = 0; for (x in [1, 2, 3]) { sum += x; } sum",
+                Object::Integer(6),
+            ),
+            ("let n = 0; for (x in []) { n += 1; } n", Object::Integer(0)),
+            // `if`s used as statements can leave the loop from nested blocks
+            (
+                "let i = 0; while (true) { i += 1; if (i > 2) { if (true) { break } } } i",
+                Object::Integer(3),
+            ),
+            (
+                "let n = 0; let i = 0; while (i < 5000) { i += 1; if (i % 2 == 0) { continue } else { n += 1 } } n",
+                Object::Integer(2500),
+            ),
+            // each iteration has its own scope
+            ("let x = 10; for (x in [1, 2]) { } x", Object::Integer(10)),
+            (
+                "let x = 10; let i = 0; while (i < 2) { let x = i; i += 1; } x",
+                Object::Integer(10),
+            ),
+            (
+                "let n = 0; for (x in [1, 2]) { for (x in [10, 20]) { n += x; } n += x; } n",
+                Object::Integer(63),
+            ),
+            (
+                "let fs = []; for (i in [1, 2, 3]) { fs = push(fs, fn() { i }); } [fs[0](), fs[2]()]",
+                Object::Array(vec![Object::Integer(1), Object::Integer(3)]),
+            ),
+            (
+                "let fs = []; let i = 0; while (i < 3) { let j = i; fs = push(fs, fn() { j }); i += 1; } [fs[0](), fs[2]()]",
+                Object::Array(vec![Object::Integer(0), Object::Integer(2)]),
+            ),
+            (
+                "let fs = []; for (i in [1, 2]) { fs = push(fs, fn() { i += 10; i }); } fs[0](); [fs[0](), fs[1]()]",
+                Object::Array(vec![Object::Integer(21), Object::Integer(12)]),
+            ),
+            (
+                "let pairs = 0; for (a in [1, 2, 3]) { for (b in [1, 2, 3]) { if (b > a) { break; } pairs += 1; } } pairs",
+                Object::Integer(6),
+            ),
+            (
+                "let s = \"\"; for (w in [\"a\", \"b\", \"c\"]) { if (w == \"b\") { continue; } s += w; } s",
+                Object::String("ac".to_string()),
+            ),
+            // the array is evaluated once
+            (
+                "let xs = [1, 2]; let n = 0; for (x in xs) { xs = push(xs, x); n += 1; } n",
+                Object::Integer(2),
+            ),
+            (
+                "let find = fn(xs, y) { let i = 0; for (x in xs) { if (x == y) { return i; } i += 1; } -1 }; [find([5, 6, 7], 7), find([], 1)]",
+                Object::Array(vec![Object::Integer(2), Object::Integer(-1)]),
+            ),
+            (
+                "let count = fn(n) { let i = 0; while (i < n) { i += 1; } i }; count(100000)",
+                Object::Integer(100000),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Ok(expected), "Wrong result for `{input}`");
+        }
+    }
+
+    #[test]
+    fn test_functions() {
+        let tests = [
+            ("let f = fn() { 5 + 10; }; f();", Object::Integer(15)),
+            ("let f = fn() { return 1; 2 }; f();", Object::Integer(1)),
+            ("let f = fn() { }; f();", Object::Null),
+            (
+                "let sum = fn(a, b) { let c = a + b; c }; sum(1, 2);",
+                Object::Integer(3),
+            ),
+            (
+                "let g = 10; let f = fn(a) { let b = a * 2; b + g }; f(1) + f(2);",
+                Object::Integer(26),
+            ),
+            ("fn(x) { x }(5)", Object::Integer(5)),
+            ("return 10; 9;", Object::Integer(10)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Ok(expected), "Wrong result for `{input}`");
+        }
+    }
+
+    #[test]
+    fn test_closures() {
+        let tests = [
+            (
+                "let new_adder = fn(a, b) { fn(c) { a + b + c } }; new_adder(1, 2)(8);",
+                11,
+            ),
+            (
+                "let new_closure = fn(a) { fn() { fn() { a } } }; new_closure(99)()();",
+                99,
+            ),
+            (
+                "let wrapper = fn() { let countdown = fn(x) { if (x == 0) { 0 } else { countdown(x - 1) } }; countdown(5) }; wrapper();",
+                0,
+            ),
+            (
+                "let fib = fn(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2) }; fib(15);",
+                610,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                run(input),
+                Ok(Object::Integer(expected)),
+                "Wrong result for `{input}`"
+            );
+        }
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let tests = [
+            ("5 + true;", "type mismatch: INTEGER + BOOLEAN"),
+            ("-true", "unknown operator: -BOOLEAN"),
+            ("true + false;", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("1 < true", "type mismatch: INTEGER < BOOLEAN"),
+            ("10 / (5 - 5)", "division by zero"),
+            (r#""Hello" - "World""#, "unknown operator: STRING - STRING"),
+            (r#""Hello" + 1"#, "type mismatch: STRING + INTEGER"),
+            ("1[0]", "index operator not supported: INTEGER[INTEGER]"),
+            ("{[1]: 2}", "unusable as hash key: ARRAY"),
+            ("{1: 2}[fn() { 1 }]", "unusable as hash key: CLOSURE"),
+            ("len(1)", "argument to `len` not supported, got INTEGER"),
+            (
+                "first(1, 2)",
+                "wrong number of arguments: expected 1, got 2",
+            ),
+            ("let x = 5; x(1)", "not a function: INTEGER"),
+            (
+                "fn(a, b) { a }(1)",
+                "wrong number of arguments: expected 2, got 1",
+            ),
+            ("let f = fn(x) { f(x) }; f(1)", "stack overflow"),
+            ("10 % 0", "division by zero"),
+            ("x = 1", "identifier not found: x"),
+            ("for (x in 5) { }", "cannot iterate over INTEGER"),
+            ("for (x in [1]) { } x", "identifier not found: x"),
+            ("for (x in [1]) { let y = x; } y", "identifier not found: y"),
+            (
+                "while (true) { let y = 1; break; } y",
+                "identifier not found: y",
+            ),
+            ("for (x in {}) { }", "cannot iterate over HASH"),
+            ("while (1 + true) { }", "type mismatch: INTEGER + BOOLEAN"),
+            ("1.5 + true", "type mismatch: FLOAT + BOOLEAN"),
+            ("{1.5: 1}", "unusable as hash key: FLOAT"),
+            ("int(0.0 / 0.0)", "cannot convert NaN to INTEGER"),
+            ("1 <= true", "type mismatch: INTEGER <= BOOLEAN"),
+            ("x += 1", "identifier not found: x"),
+            ("len += 1", "cannot assign to builtin: len"),
+            ("let x = 1; x += true", "type mismatch: INTEGER + BOOLEAN"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                run(input),
+                Err(expected.to_string()),
+                "Wrong result for `{input}`"
+            );
+        }
+    }
+
+    // ===========================================
+    // HELPERS
+    fn run(input: &str) -> Result<Object, String> {
+        let mut lexer = Lexer::new(input);
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program();
+        assert!(
+            parser.get_errors().is_empty(),
+            "Parser had errors: {:?}",
+            parser.get_errors()
+        );
+
+        let mut compiler = Compiler::new();
+        compiler.compile(&program)?;
+
+        let mut vm = Vm::new(compiler.bytecode());
+        vm.run()?;
+
+        return Ok(vm.last_popped_stack_elem().clone());
+    }
+}